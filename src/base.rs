@@ -22,6 +22,57 @@ pub struct Job {
 
     /// Path to a script file that defining how to start computation
     pub run_file: PathBuf,
+
+    /// The wall-time limit in seconds. The job will be terminated when
+    /// running out of time.
+    pub timeout: Option<f64>,
+
+    /// The policy for retrying the job when failed.
+    pub retry: RetryPolicy,
+
+    /// The computational resources required on each node.
+    pub requires: Resources,
+
+    /// The number of nodes required. The job will be started on the first
+    /// node, with host names of all nodes in environment variable
+    /// `GOSH_NODES` separated by comma.
+    pub nodes: usize,
+
+    /// The environment variables for running the job.
+    pub env: std::collections::BTreeMap<String, String>,
+
+    /// The constraints on node labels for placing the job.
+    pub affinity: Affinity,
+
+    /// The priority for dispatching the job. Jobs with higher priority
+    /// will be dispatched first.
+    pub priority: i32,
+
+    /// The IDs of jobs which should complete successfully before the job
+    /// can be dispatched. The job will be cancelled if any of them failed.
+    pub depends_on: Vec<crate::JobId>,
+
+    /// Run the job once all jobs in `depends_on` finished, even if some
+    /// of them failed.
+    pub always_run: bool,
+
+    /// The key for placing the job near previous jobs, usually the working
+    /// directory on shared filesystem. The job prefers the node which ran
+    /// the last job with the same key, for reusing node-local caches.
+    pub locality: Option<String>,
+
+    /// The input files read by the job. The result of a completed job
+    /// may be reused for jobs with the same script, environment variables
+    /// and input file contents, if the scheduler caches results.
+    pub inputs: Vec<PathBuf>,
+
+    /// Always run the job instead of reusing a cached result.
+    pub no_cache: bool,
+
+    /// The token identifying this run of the job on the worker, assigned
+    /// by the scheduler on dispatch. Unlike the name, it is unique among
+    /// jobs running on a worker.
+    token: Option<String>,
 }
 
 impl Default for Job {
@@ -32,6 +83,19 @@ impl Default for Job {
             out_file: "job.out".into(),
             err_file: "job.err".into(),
             run_file: "run".into(),
+            timeout: None,
+            retry: RetryPolicy::default(),
+            requires: Resources::default(),
+            nodes: 1,
+            env: Default::default(),
+            affinity: Affinity::default(),
+            priority: 0,
+            depends_on: vec![],
+            always_run: false,
+            locality: None,
+            inputs: vec![],
+            no_cache: false,
+            token: None,
         }
     }
 }
@@ -60,9 +124,199 @@ impl Job {
     pub fn name(&self) -> String {
         self.name.clone()
     }
+
+    /// Set the token identifying this run of the job on the worker.
+    pub(crate) fn with_token(mut self, token: String) -> Self {
+        self.token = Some(token);
+        self
+    }
+
+    /// Return the token identifying this run of the job on the worker,
+    /// or the job name if not assigned.
+    pub(crate) fn token(&self) -> String {
+        self.token.clone().unwrap_or_else(|| self.name())
+    }
+
+    /// Set wall-time limit in seconds.
+    pub fn with_timeout(mut self, timeout: f64) -> Self {
+        self.timeout = timeout.into();
+        self
+    }
+
+    /// Set the policy for retrying the job when failed.
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Set the computational resources required on each of `nodes` nodes.
+    pub fn with_requires(mut self, requires: Resources, nodes: usize) -> Self {
+        assert!(nodes > 0, "number of nodes cannot be zero!");
+        self.requires = requires;
+        self.nodes = nodes;
+        self
+    }
+
+    /// Set environment variable `key` for running the job.
+    pub fn with_env(mut self, key: &str, value: impl Into<String>) -> Self {
+        self.env.insert(key.into(), value.into());
+        self
+    }
+
+    /// Set the constraints on node labels for placing the job.
+    pub fn with_affinity(mut self, affinity: Affinity) -> Self {
+        self.affinity = affinity;
+        self
+    }
+
+    /// Set the priority for dispatching the job.
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Set the key for placing the job near previous jobs with the same
+    /// key.
+    pub fn with_locality(mut self, key: &str) -> Self {
+        self.locality = Some(key.into());
+        self
+    }
+
+    /// Declare the input files read by the job, for caching its result.
+    pub fn with_inputs(mut self, inputs: Vec<PathBuf>) -> Self {
+        self.inputs = inputs;
+        self
+    }
+
+    /// Always run the job instead of reusing a cached result if
+    /// `no_cache` is true.
+    pub fn with_no_cache(mut self, no_cache: bool) -> Self {
+        self.no_cache = no_cache;
+        self
+    }
+
+    /// Hold the job until jobs `depends_on` finished. If `always_run` is
+    /// false, the job will be cancelled when any of them failed.
+    pub fn with_dependencies(mut self, depends_on: Vec<crate::JobId>, always_run: bool) -> Self {
+        self.depends_on = depends_on;
+        self.always_run = always_run;
+        self
+    }
+}
+
+/// Node labels in `key=value` form, e.g. `partition=fat` or `rack=3`.
+pub type Labels = std::collections::BTreeMap<String, String>;
+
+/// Parse a node label from string in `key=value` form.
+pub fn parse_label(s: &str) -> Result<(String, String)> {
+    let (key, value) = s.split_once('=').ok_or(format_err!("invalid label {s:?}, expect key=value"))?;
+    let key = key.trim();
+    ensure!(!key.is_empty(), "empty label key in {s:?}");
+    Ok((key.into(), value.trim().into()))
+}
+
+/// The constraints on node labels for placing a job.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Affinity {
+    /// The job can only be placed on nodes having all these labels.
+    pub selector: Labels,
+    /// The job cannot be placed on nodes having any of these labels.
+    pub anti_affinity: Labels,
+}
+
+impl Affinity {
+    /// Return true if a node with `labels` satisfies the constraints.
+    pub fn matches(&self, labels: &Labels) -> bool {
+        let has = |(k, v): (&String, &String)| labels.get(k) == Some(v);
+        self.selector.iter().all(has) && !self.anti_affinity.iter().any(has)
+    }
+}
+
+/// Computational resources required by a job, or provided by a node.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Resources {
+    /// The number of CPU cores
+    pub cores: usize,
+    /// The size of memory in GiB
+    pub memory: f64,
+}
+
+impl Resources {
+    /// Detect resources available on this machine.
+    pub fn detect() -> Result<Self> {
+        let cores = std::thread::available_parallelism()?.get();
+        let meminfo = gut::fs::read_file("/proc/meminfo")?;
+        let kb: f64 = meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix("MemTotal:"))
+            .and_then(|x| x.trim().trim_end_matches("kB").trim().parse().ok())
+            .ok_or(format_err!("no MemTotal in /proc/meminfo"))?;
+        let memory = kb / 1024.0 / 1024.0;
+        Ok(Self { cores, memory })
+    }
+
+    /// Return true if `self` fits in `available` resources.
+    pub fn fits_in(&self, available: &Resources) -> bool {
+        self.cores <= available.cores && self.memory <= available.memory
+    }
+
+    fn plus(&self, other: &Resources) -> Self {
+        Self {
+            cores: self.cores + other.cores,
+            memory: self.memory + other.memory,
+        }
+    }
+
+    fn minus(&self, other: &Resources) -> Self {
+        Self {
+            cores: self.cores.saturating_sub(other.cores),
+            memory: (self.memory - other.memory).max(0.0),
+        }
+    }
 }
 
-fn random_name() -> String {
+/// The policy for retrying a failed job, preferably on a different node.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts to run the job, including the
+    /// first one.
+    pub max_attempts: usize,
+
+    /// Retry when the job exited unsuccessfully. By default, only
+    /// transport errors with remote node will be retried.
+    pub on_failure: bool,
+
+    /// The delay in seconds before the first retry, which will be doubled
+    /// for each subsequent retry.
+    pub backoff: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            on_failure: false,
+            backoff: 1.0,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Return the delay in seconds before retrying a job that has failed
+    /// `failures` times. Return None if no more attempts allowed.
+    pub fn delay(&self, failures: usize) -> Option<f64> {
+        if failures >= self.max_attempts {
+            return None;
+        }
+        let n = failures.saturating_sub(1).min(16) as i32;
+        Some(self.backoff * 2f64.powi(n))
+    }
+}
+
+pub(crate) fn random_name() -> String {
     use rand::distributions::Alphanumeric;
     use rand::Rng;
 
@@ -78,12 +332,30 @@ fn random_name() -> String {
 // [[file:../remote.note::769262a8][769262a8]]
 mod node {
     use super::*;
-    use crossbeam_channel::{unbounded, Receiver, Sender};
+    use crate::policy::{Candidate, Policy, SchedulingPolicy};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    use tokio::sync::Notify;
+    use tokio_util::sync::CancellationToken;
 
     /// Represents a remote node for computation
     #[derive(Debug, Clone, Deserialize, Serialize)]
     pub struct Node {
         name: String,
+        /// The number of jobs can be run on the node at the same time
+        #[serde(default = "default_slots")]
+        slots: usize,
+        /// The computational resources provided by the node. None for
+        /// unknown, which fits any job.
+        #[serde(default)]
+        capacity: Option<Resources>,
+        /// The labels for matching job affinity constraints.
+        #[serde(default)]
+        labels: Labels,
+    }
+
+    fn default_slots() -> usize {
+        1
     }
 
     impl Node {
@@ -91,13 +363,57 @@ mod node {
         pub fn name(&self) -> &str {
             &self.name
         }
+
+        /// Return the host name of remote node, without the port part.
+        pub fn host(&self) -> &str {
+            self.name.split(':').next().unwrap_or(&self.name)
+        }
+
+        /// Return the number of job slots on remote node
+        pub fn slots(&self) -> usize {
+            self.slots
+        }
+
+        /// Set the number of job slots on remote node.
+        pub fn with_slots(mut self, slots: usize) -> Self {
+            assert!(slots > 0, "node slots cannot be zero!");
+            self.slots = slots;
+            self
+        }
+
+        /// Return the computational resources provided by remote node.
+        pub fn capacity(&self) -> Option<&Resources> {
+            self.capacity.as_ref()
+        }
+
+        /// Set the computational resources provided by remote node.
+        pub fn with_capacity(mut self, capacity: Resources) -> Self {
+            self.capacity = capacity.into();
+            self
+        }
+
+        /// Return the labels of remote node.
+        pub fn labels(&self) -> &Labels {
+            &self.labels
+        }
+
+        /// Add a label `key=value` to remote node.
+        pub fn with_label(mut self, key: &str, value: &str) -> Self {
+            self.labels.insert(key.into(), value.into());
+            self
+        }
     }
 
     impl<T: Into<String>> From<T> for Node {
         fn from(node: T) -> Self {
             let name = node.into();
             assert!(!name.is_empty(), "node name cannot be empty!");
-            Self { name }
+            Self {
+                name,
+                slots: default_slots(),
+                capacity: None,
+                labels: Labels::new(),
+            }
         }
     }
 
@@ -108,46 +424,358 @@ mod node {
         }
     }
 
+    /// The state of a registered remote node
+    struct NodeRecord {
+        node: Node,
+        /// Whether the node is unreachable.
+        down: bool,
+        /// The number of jobs running on the node.
+        running: usize,
+        /// The resources allocated for running jobs.
+        used: Resources,
+        /// Will be cancelled when the node is found down.
+        down_token: CancellationToken,
+        /// The last time a slot of the node became free.
+        released: Instant,
+        /// The jobs left on the node when they were requeued as the node
+        /// was found down, to be cancelled once reachable.
+        stale_jobs: Vec<String>,
+    }
+
+    impl NodeRecord {
+        fn new(node: Node) -> Self {
+            Self {
+                node,
+                down: false,
+                running: 0,
+                used: Resources::default(),
+                down_token: CancellationToken::new(),
+                released: Instant::now(),
+                stale_jobs: vec![],
+            }
+        }
+
+        /// Return true if the node has free slot and enough resources for
+        /// running a job requiring `requires`, and its labels satisfy
+        /// `affinity`.
+        fn can_fit(&self, requires: &Resources, affinity: &Affinity) -> bool {
+            if self.down || self.running >= self.node.slots || !affinity.matches(&self.node.labels) {
+                return false;
+            }
+            match &self.node.capacity {
+                Some(capacity) => requires.fits_in(&capacity.minus(&self.used)),
+                None => true,
+            }
+        }
+    }
+
+    /// Return nodes in `records` which can fit `requires` and `affinity`.
+    fn candidates(records: &[NodeRecord], requires: &Resources, affinity: &Affinity) -> Vec<Candidate> {
+        records
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.can_fit(requires, affinity))
+            .map(|(index, r)| Candidate {
+                index,
+                slots: r.node.slots,
+                running: r.running,
+                released: r.released,
+            })
+            .collect()
+    }
+
+    /// Select `n` nodes from `candidates` in `records` using `policy`,
+    /// preferring those not in `avoid`. Return their indices.
+    fn select_nodes(
+        records: &[NodeRecord],
+        candidates: Vec<Candidate>,
+        n: usize,
+        avoid: &[Node],
+        policy: &dyn SchedulingPolicy,
+    ) -> Option<Vec<usize>> {
+        let (preferred, avoided): (Vec<_>, Vec<_>) = candidates
+            .into_iter()
+            .partition(|c| !avoid.iter().any(|x| x.name == records[c.index].node.name));
+        if preferred.len() + avoided.len() < n {
+            return None;
+        }
+        let m = n.min(preferred.len());
+        let mut selected = policy.choose(&preferred, m);
+        if m < n {
+            selected.extend(policy.choose(&avoided, n - m));
+        }
+        Some(selected)
+    }
+
+    /// Borrow `selected` nodes in `records` for a job requiring `requires`.
+    fn borrow_nodes(records: &mut [NodeRecord], selected: &[usize], requires: &Resources) -> Vec<Node> {
+        let nodes = selected
+            .iter()
+            .map(|&i| {
+                let r = &mut records[i];
+                r.running += 1;
+                r.used = r.used.plus(requires);
+                r.node.clone()
+            })
+            .collect_vec();
+        info!("client borrowed nodes: {:?}", nodes.iter().map(|x| x.name()).collect_vec());
+        nodes
+    }
+
+    /// The usage of a registered remote node, for inspection
+    #[derive(Debug, Clone, Deserialize, Serialize)]
+    pub struct NodeUsage {
+        /// The remote node
+        pub node: Node,
+        /// Whether the node is unreachable
+        pub down: bool,
+        /// The number of jobs running on the node
+        pub running: usize,
+        /// The resources allocated for running jobs
+        pub used: Resources,
+    }
+
     /// Represents a list of remote nodes allocated for computation
     #[derive(Clone)]
     pub struct Nodes {
-        rx: Receiver<Node>,
-        tx: Sender<Node>,
+        // all registered nodes in order, and a notifier for waking up
+        // waiters when any node becomes available
+        inner: Arc<(Mutex<Vec<NodeRecord>>, Notify)>,
+        // for choosing nodes among those can fit a job
+        policy: Arc<dyn SchedulingPolicy>,
     }
 
     impl Nodes {
         /// Construct `Nodes` from a list of nodes.
         pub fn new<T: Into<Node>>(nodes: impl IntoIterator<Item = T>) -> Self {
-            let (tx, rx) = unbounded();
             let nodes = nodes.into_iter().collect_vec();
             let n = nodes.len();
             info!("We have {n} nodes in totoal for computation.");
+            let pool = Self {
+                inner: Default::default(),
+                policy: Arc::new(crate::policy::Fifo),
+            };
             for node in nodes {
-                tx.send(node.into()).unwrap();
+                pool.add_node(node.into()).unwrap();
             }
-            Self { rx, tx }
+            pool
+        }
+
+        /// Set the policy for choosing nodes to borrow.
+        pub fn with_policy(mut self, policy: Policy) -> Self {
+            info!("use scheduling policy {policy:?}");
+            self.policy = policy.build().into();
+            self
         }
 
-        /// Return the number of nodes
+        /// Return the number of free slots on nodes being up
         pub fn len(&self) -> usize {
-            self.rx.len()
+            let records = self.inner.0.lock().unwrap();
+            records
+                .iter()
+                .filter(|r| !r.down)
+                .map(|r| r.node.slots.saturating_sub(r.running))
+                .sum()
+        }
+
+        /// Return all registered nodes, including those being down.
+        pub fn registered(&self) -> Vec<Node> {
+            let records = self.inner.0.lock().unwrap();
+            records.iter().map(|r| r.node.clone()).collect()
         }
 
-        /// Borrow one node from `Nodes`
-        pub fn borrow_node(&self) -> Result<Node> {
-            let node = self.rx.recv()?;
-            let name = &node.name;
-            info!("client borrowed one node: {name:?}");
-            Ok(node)
+        /// Return true if `node` is registered and not down.
+        pub fn is_up(&self, node: &Node) -> bool {
+            let records = self.inner.0.lock().unwrap();
+            records.iter().any(|r| r.node.name == node.name && !r.down)
         }
 
-        /// Return one `node` to `Nodes`
-        pub fn return_node(&self, node: Node) -> Result<()> {
-            let name = &node.name;
-            info!("client returned node {name:?}");
-            self.tx.send(node)?;
+        /// Return true if a job requiring `requires` on each of `n` nodes
+        /// with labels satisfying `affinity` could ever be placed on
+        /// registered nodes, when they are all free. Always true if no
+        /// node registered yet.
+        pub fn could_fit(&self, requires: &Resources, affinity: &Affinity, n: usize) -> bool {
+            let records = self.inner.0.lock().unwrap();
+            if records.is_empty() {
+                return true;
+            }
+            let fits = |r: &&NodeRecord| {
+                affinity.matches(&r.node.labels) && r.node.capacity.as_ref().is_none_or(|c| requires.fits_in(c))
+            };
+            records.iter().filter(fits).count() >= n
+        }
+
+        /// Return the usage of all registered nodes.
+        pub fn usage(&self) -> Vec<NodeUsage> {
+            let records = self.inner.0.lock().unwrap();
+            records
+                .iter()
+                .map(|r| NodeUsage {
+                    node: r.node.clone(),
+                    down: r.down,
+                    running: r.running,
+                    used: r.used.clone(),
+                })
+                .collect()
+        }
+
+        /// Register a new `node` into `Nodes`, which can be borrowed as
+        /// many times as its slots. Adding an already registered node will
+        /// update its slots and capacity, and bring it up if it was down.
+        pub fn add_node(&self, node: Node) -> Result<()> {
+            let (lock, notify) = &*self.inner;
+            let mut records = lock.lock().unwrap();
+            info!(
+                "add node {node} with {} slots, capacity: {:?}, labels: {:?}",
+                node.slots, node.capacity, node.labels
+            );
+            if let Some(r) = records.iter_mut().find(|r| r.node.name == node.name) {
+                r.node = node;
+                if r.down {
+                    r.down = false;
+                    r.down_token = CancellationToken::new();
+                }
+            } else {
+                records.push(NodeRecord::new(node));
+            }
+            notify.notify_waiters();
             Ok(())
         }
+
+        /// Return true if `n` nodes with labels satisfying `affinity` are
+        /// available for now to a job requiring `requires` on each node.
+        pub fn can_fit_now(&self, requires: &Resources, affinity: &Affinity, n: usize) -> bool {
+            let records = self.inner.0.lock().unwrap();
+            candidates(&records, requires, affinity).len() >= n
+        }
+
+        /// Try to borrow `n` nodes from `Nodes` with enough resources for a
+        /// job requiring `requires` on each node and with labels satisfying
+        /// `affinity`, preferring nodes not in `avoid`. Return None if no
+        /// such nodes available for now. Nodes being down will be skipped.
+        pub fn try_borrow_nodes(
+            &self,
+            requires: &Resources,
+            affinity: &Affinity,
+            n: usize,
+            avoid: &[Node],
+        ) -> Option<Vec<Node>> {
+            let mut records = self.inner.0.lock().unwrap();
+            let candidates = candidates(&records, requires, affinity);
+            let selected = select_nodes(&records, candidates, n, avoid, self.policy.as_ref())?;
+            Some(borrow_nodes(&mut records, &selected, requires))
+        }
+
+        /// Try to borrow `n` nodes like `try_borrow_nodes`, but starting
+        /// with `node`. Return None if `node` cannot fit for now.
+        pub fn try_borrow_nodes_from(
+            &self,
+            node: &Node,
+            requires: &Resources,
+            affinity: &Affinity,
+            n: usize,
+            avoid: &[Node],
+        ) -> Option<Vec<Node>> {
+            let mut records = self.inner.0.lock().unwrap();
+            let mut candidates = candidates(&records, requires, affinity);
+            let i = candidates.iter().position(|c| records[c.index].node.name == node.name)?;
+            let first = candidates.remove(i);
+            let mut selected = vec![first.index];
+            selected.extend(select_nodes(&records, candidates, n - 1, avoid, self.policy.as_ref())?);
+            Some(borrow_nodes(&mut records, &selected, requires))
+        }
+
+        /// Return a notifier which will wake up all waiters when any node
+        /// may become available for borrowing.
+        pub fn changed(&self) -> &Notify {
+            &self.inner.1
+        }
+
+        /// Return `nodes` borrowed for a job requiring `requires` back to
+        /// `Nodes`.
+        pub fn return_nodes(&self, nodes: &[Node], requires: &Resources) -> Result<()> {
+            let (lock, notify) = &*self.inner;
+            let mut records = lock.lock().unwrap();
+            for node in nodes {
+                let name = &node.name;
+                let r = records
+                    .iter_mut()
+                    .find(|r| &r.node.name == name)
+                    .ok_or(format_err!("return an unregistered node {name:?}"))?;
+                info!("client returned node {name:?}");
+                r.running = r.running.saturating_sub(1);
+                r.used = r.used.minus(requires);
+                r.released = Instant::now();
+            }
+            notify.notify_waiters();
+            Ok(())
+        }
+
+        /// Remove nodes accepted by `filter` which have been idle for at
+        /// least `idle`, either running no job or being down. Return the
+        /// removed nodes.
+        pub fn retire_idle(&self, idle: Duration, filter: impl Fn(&Node) -> bool) -> Vec<Node> {
+            let mut records = self.inner.0.lock().unwrap();
+            let (retired, kept) = std::mem::take(&mut *records)
+                .into_iter()
+                .partition(|r| filter(&r.node) && r.running == 0 && r.released.elapsed() >= idle);
+            *records = kept;
+            retired.into_iter().map(|r: NodeRecord| r.node).collect()
+        }
+
+        /// Mark `node` as down. The node will not be borrowed until it is
+        /// marked up again. Return true if the node was up.
+        pub fn mark_down(&self, node: &Node) -> bool {
+            let mut records = self.inner.0.lock().unwrap();
+            let Some(r) = records.iter_mut().find(|r| r.node.name == node.name && !r.down) else {
+                return false;
+            };
+            warn!("node {node} is down");
+            r.down = true;
+            r.down_token.cancel();
+            true
+        }
+
+        /// Mark `node` as up, which can be borrowed again. Return true if
+        /// the node was down.
+        pub fn mark_up(&self, node: &Node) -> Result<bool> {
+            let (lock, notify) = &*self.inner;
+            let mut records = lock.lock().unwrap();
+            let Some(r) = records.iter_mut().find(|r| r.node.name == node.name && r.down) else {
+                return Ok(false);
+            };
+            info!("node {node} is up again");
+            r.down = false;
+            r.down_token = CancellationToken::new();
+            notify.notify_waiters();
+            Ok(true)
+        }
+
+        /// Record job `token` left on `node` for cancelling it later.
+        pub fn add_stale_job(&self, node: &Node, token: &str) {
+            let mut records = self.inner.0.lock().unwrap();
+            if let Some(r) = records.iter_mut().find(|r| r.node.name == node.name) {
+                r.stale_jobs.push(token.into());
+            }
+        }
+
+        /// Take out the jobs recorded as left on `node`.
+        pub fn take_stale_jobs(&self, node: &Node) -> Vec<String> {
+            let mut records = self.inner.0.lock().unwrap();
+            match records.iter_mut().find(|r| r.node.name == node.name) {
+                Some(r) => std::mem::take(&mut r.stale_jobs),
+                None => vec![],
+            }
+        }
+
+        /// Return a token which will be cancelled when `node` is found down.
+        pub fn down_token(&self, node: &Node) -> CancellationToken {
+            let records = self.inner.0.lock().unwrap();
+            match records.iter().find(|r| r.node.name == node.name) {
+                Some(r) => r.down_token.clone(),
+                None => CancellationToken::new(),
+            }
+        }
     }
 }
 // 769262a8 ends here
@@ -159,21 +787,113 @@ use gosh_runner::prelude::SpawnSessionExt;
 use gosh_runner::process::Session;
 
 use tempfile::TempDir;
+use tokio_util::sync::CancellationToken;
 // e19bce71 ends here
 
 // [[file:../remote.note::955c926a][955c926a]]
+/// The time in seconds to wait after SIGTERM before killing the processes
+/// in job session by SIGKILL.
+const TERMINATE_GRACE_PERIOD: f64 = 5.0;
+
 /// Computation represents a submitted `Job`
 pub struct Computation {
     job: Job,
 
     /// command session. The drop order is above Tempdir
-    session: Option<Session<tokio::process::Child>>,
+    session: Option<Session<std::process::Child>>,
 
     /// The working directory of computation
     wrk_dir: TempDir,
+
+    /// For cancelling the computation from outside
+    cancel: CancellationToken,
+
+    /// Whether the computation has been terminated for running out of time
+    timed_out: bool,
+
+    /// The time when the job process started
+    started: Option<std::time::Instant>,
+
+    /// The resource usage of the job process after it exited
+    usage: Option<JobUsage>,
 }
 // 955c926a ends here
 
+// [[file:../remote.note::d7255ce6][d7255ce6]]
+use std::process::ExitStatus;
+
+/// The exit status and resource usage of a job process, for accounting
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+pub struct JobUsage {
+    /// The exit code if the process exited normally
+    pub exit_code: Option<i32>,
+    /// The signal number if the process was killed by a signal
+    pub signal: Option<i32>,
+    /// The elapsed wall time in seconds
+    pub wall_time: f64,
+    /// The user CPU time in seconds
+    pub user_time: f64,
+    /// The system CPU time in seconds
+    pub system_time: f64,
+    /// The maximum resident set size in KiB
+    pub max_rss: u64,
+}
+
+impl JobUsage {
+    /// Construct from `status` and `rusage` returned by wait4, and `wall_time`
+    /// in seconds. The CPU time and memory include all descendants waited
+    /// for by the process.
+    fn new(status: ExitStatus, rusage: &nix::libc::rusage, wall_time: f64) -> Self {
+        use std::os::unix::process::ExitStatusExt;
+
+        let seconds = |t: nix::libc::timeval| t.tv_sec as f64 + t.tv_usec as f64 * 1e-6;
+        Self {
+            exit_code: status.code(),
+            signal: status.signal(),
+            wall_time,
+            user_time: seconds(rusage.ru_utime),
+            system_time: seconds(rusage.ru_stime),
+            // in KiB on Linux
+            max_rss: rusage.ru_maxrss as u64,
+        }
+    }
+}
+
+/// Wait for child process `pid` to exit, and return its exit status with
+/// its resource usage. The process is reaped by wait4 on SIGCHLD, as
+/// `Child::wait` reaps it without keeping the resource usage.
+///
+/// The child should be spawned by `std::process::Command`, and never be
+/// waited by its `Child`, which does not reap the process even when
+/// dropped. So the pid is reaped only here, and cannot be reused by
+/// another process before that. A child spawned by tokio could be reaped
+/// by tokio behind our back, e.g. when its `Child` dropped, and then wait4
+/// would fail with ECHILD or even reap an unrelated process reusing the
+/// pid.
+async fn wait4(pid: u32) -> Result<(ExitStatus, nix::libc::rusage)> {
+    use nix::libc;
+    use std::os::unix::process::ExitStatusExt;
+    use tokio::signal::unix::{signal, SignalKind};
+
+    // listen before checking, so that no SIGCHLD will be missed
+    let mut sigchld = signal(SignalKind::child())?;
+    loop {
+        let mut status = 0;
+        // SAFETY: rusage is a plain C struct, for which all zero is valid
+        let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+        // SAFETY: both pointers are valid for writing during the call
+        let ret = unsafe { libc::wait4(pid as libc::pid_t, &mut status, libc::WNOHANG, &mut rusage) };
+        match ret {
+            0 => {
+                sigchld.recv().await;
+            }
+            -1 => return Err(std::io::Error::last_os_error()).context("wait4"),
+            _ => return Ok((ExitStatus::from_raw(status), rusage)),
+        }
+    }
+}
+// d7255ce6 ends here
+
 // [[file:../remote.note::a65e6dae][a65e6dae]]
 impl Computation {
     /// The full path to the working directory for running the job.
@@ -182,7 +902,7 @@ impl Computation {
     }
 
     /// The full path to computation output file (stdout).
-    fn out_file(&self) -> PathBuf {
+    pub(crate) fn out_file(&self) -> PathBuf {
         self.wrk_dir().join(&self.job.out_file)
     }
 
@@ -191,6 +911,15 @@ impl Computation {
         self.wrk_dir().join(&self.job.err_file)
     }
 
+    /// The directory in which the job runs: the working directory of a
+    /// cmd job, or the scratch directory otherwise.
+    pub(crate) fn job_dir(&self) -> PathBuf {
+        match &self.job.locality {
+            Some(dir) => dir.into(),
+            None => std::fs::canonicalize(self.wrk_dir()).unwrap_or_else(|_| self.wrk_dir().into()),
+        }
+    }
+
     /// The full path to the script for running the job.
     fn run_file(&self) -> PathBuf {
         self.wrk_dir().join(&self.job.run_file)
@@ -224,6 +953,10 @@ impl Computation {
             job,
             wrk_dir: wdir.into(),
             session: None,
+            cancel: CancellationToken::new(),
+            timed_out: false,
+            started: None,
+            usage: None,
         };
 
         session.create_run_file()?;
@@ -231,18 +964,70 @@ impl Computation {
         Ok(session)
     }
 
+    /// Terminate all processes in the `session`. The processes still alive
+    /// after a grace period since SIGTERM will be killed by SIGKILL.
+    async fn terminate_session(session: &mut Session<std::process::Child>) -> Result<(ExitStatus, nix::libc::rusage)> {
+        use nix::sys::signal::{killpg, Signal};
+        use nix::unistd::Pid;
+
+        let pid = session.child.id();
+        let handler = session.handler().clone();
+        info!("terminating session {:?}", handler.id());
+        // The session leader leads its own process group, and its pid can
+        // not be reused before reaped by us. Resume the processes before
+        // SIGTERM in case they were paused, as `SessionHandler::terminate`
+        // does, but without blocking the runtime while waiting.
+        let pgid = Pid::from_raw(pid as i32);
+        if let Err(err) = killpg(pgid, Signal::SIGCONT) {
+            warn!("failed to resume session: {err:?}");
+        }
+        tokio::time::sleep(std::time::Duration::from_secs_f64(0.2)).await;
+        if let Err(err) = killpg(pgid, Signal::SIGTERM) {
+            warn!("failed to terminate session: {err:?}");
+        }
+        let grace = std::time::Duration::from_secs_f64(TERMINATE_GRACE_PERIOD);
+        if let Ok(exited) = tokio::time::timeout(grace, wait4(pid)).await {
+            return exited;
+        }
+        warn!("session still alive after {TERMINATE_GRACE_PERIOD} seconds, killing it ...");
+        for p in handler.get_processes()? {
+            p.send_signal("SIGKILL")?;
+        }
+        wait4(pid).await
+    }
+
     /// Wait for background command to complete.
     async fn wait(&mut self) -> Result<()> {
+        let timeout = self.job.timeout;
         if let Some(s) = self.session.as_mut() {
-            let ecode = s.child.wait().await?;
+            let time_out = async {
+                match timeout {
+                    Some(t) => tokio::time::sleep(std::time::Duration::from_secs_f64(t)).await,
+                    None => std::future::pending().await,
+                }
+            };
+            let exited = tokio::select! {
+                exited = wait4(s.child.id()) => Some(exited?),
+                _ = self.cancel.cancelled() => None,
+                _ = time_out => {
+                    self.timed_out = true;
+                    None
+                }
+            };
+            let wall_time = || self.started.map(|t| t.elapsed().as_secs_f64()).unwrap_or_default();
+            let Some((ecode, rusage)) = exited else {
+                let (status, rusage) = Self::terminate_session(s).await?;
+                self.usage = JobUsage::new(status, &rusage, wall_time()).into();
+                if self.timed_out {
+                    bail!("Job terminated for running out of time ({} seconds).", timeout.unwrap_or_default());
+                }
+                bail!("Job cancelled.");
+            };
             info!("job session exited: {}", ecode);
+            self.usage = JobUsage::new(ecode, &rusage, wall_time()).into();
             if !ecode.success() {
                 error!("job exited unsuccessfully!");
-                let txt = gut::fs::read_file(self.run_file())?;
-                let run = format!("run file: {txt:?}");
-                let txt = gut::fs::read_file(self.err_file())?;
-                let err = format!("stderr: {txt:?}");
-                bail!("Job failed with error:\n{run:?}{err:?}");
+                bail!("Job exited unsuccessfully ({ecode}).");
             }
             Ok(())
         } else {
@@ -256,36 +1041,55 @@ impl Computation {
         let wdir = self.wrk_dir();
         trace!("job work direcotry: {}", wdir.display());
 
-        let mut session = tokio::process::Command::new(&program)
+        // redirect stdout and stderr to files for user inspection
+        let stdout = std::fs::File::create(self.out_file())?;
+        let stderr = std::fs::File::create(self.err_file())?;
+        // spawned by std instead of tokio, for reaping it by wait4 safely
+        let session = std::process::Command::new(&program)
             .current_dir(wdir)
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
+            .envs(&self.job.env)
+            .stdout(stdout)
+            .stderr(stderr)
             .spawn_session()?;
 
-        let mut stdout = session
-            .child
-            .stdout
-            .take()
-            .expect("child did not have a handle to stdout");
-        let mut stderr = session
-            .child
-            .stderr
-            .take()
-            .expect("child did not have a handle to stderr");
-
-        // redirect stdout and stderr to files for user inspection.
-        let mut fout = tokio::fs::File::create(self.out_file()).await?;
-        let mut ferr = tokio::fs::File::create(self.err_file()).await?;
-        tokio::io::copy(&mut stdout, &mut fout).await?;
-        tokio::io::copy(&mut stderr, &mut ferr).await?;
-
         let sid = session.handler().id();
         debug!("command running in session {:?}", sid);
         self.session = session.into();
+        self.started = std::time::Instant::now().into();
 
         Ok(())
     }
 
+    /// Return a token for cancelling the computation. Cancelling it will
+    /// terminate all processes in the session.
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// Return true if the computation has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// Return true if the computation has been terminated for running out
+    /// of time.
+    pub fn is_timed_out(&self) -> bool {
+        self.timed_out
+    }
+
+    /// Return the exit status and resource usage of the job process, if
+    /// it has exited.
+    pub fn usage(&self) -> Option<JobUsage> {
+        self.usage
+    }
+
+    /// Return the standard output and standard error written so far.
+    pub fn read_output(&self) -> Result<(String, String)> {
+        let stdout = gut::fs::read_file(self.out_file())?;
+        let stderr = gut::fs::read_file(self.err_file())?;
+        Ok((stdout, stderr))
+    }
+
     /// Start computation, and wait and return its standard output
     pub async fn wait_for_output(&mut self) -> Result<String> {
         self.start().await?;
@@ -294,6 +1098,36 @@ impl Computation {
         Ok(txt)
     }
 }
+
+impl Drop for Computation {
+    // The job is still running if the computation is dropped before
+    // waited, e.g. when the request for running it was dropped. Kill all
+    // processes in its session, or the job would run on unattended.
+    fn drop(&mut self) {
+        let Some(session) = self.session.as_ref() else { return };
+        if self.usage.is_some() {
+            return;
+        }
+        let handler = session.handler();
+        warn!("computation dropped while running, killing session {:?}", handler.id());
+        match handler.get_processes() {
+            Ok(processes) => {
+                for p in processes {
+                    if let Err(err) = p.send_signal("SIGKILL") {
+                        warn!("failed to kill process {}: {err:?}", p.id());
+                    }
+                }
+            }
+            Err(err) => warn!("failed to list processes in session: {err:?}"),
+        }
+        // reap the killed process, as nobody else will
+        let pid = session.child.id();
+        std::thread::spawn(move || {
+            // SAFETY: a null rusage pointer is allowed
+            unsafe { nix::libc::wait4(pid as nix::libc::pid_t, std::ptr::null_mut(), 0, std::ptr::null_mut()) };
+        });
+    }
+}
 // f8672e0c ends here
 
 // [[file:../remote.note::9b7911ae][9b7911ae]]
@@ -370,7 +1204,7 @@ impl Drop for LockFile {
 // 9b7911ae ends here
 
 // [[file:../remote.note::4a28f1b7][4a28f1b7]]
-pub use node::{Node, Nodes};
+pub use node::{Node, NodeUsage, Nodes};
 // 4a28f1b7 ends here
 
 // [[file:../remote.note::f725ca9b][f725ca9b]]
@@ -379,4 +1213,245 @@ fn test_node() {
     let localhost: Node = "localhost".into();
     assert_eq!(localhost.name(), "localhost");
 }
+
+#[test]
+fn test_nodes_down() -> Result<()> {
+    let nodes = Nodes::new(["node1", "node2"]);
+    assert_eq!(nodes.registered().len(), 2);
+
+    let req = Resources::default();
+    let any = Affinity::default();
+    let node1: Node = "node1".into();
+    let token = nodes.down_token(&node1);
+    assert!(nodes.mark_down(&node1));
+    assert!(!nodes.mark_down(&node1));
+    assert!(token.is_cancelled());
+    // node1 is skipped when borrowing
+    let node2 = nodes.try_borrow_nodes(&req, &any, 1, &[]).unwrap();
+    assert_eq!(node2[0].name(), "node2");
+    assert_eq!(nodes.len(), 0);
+
+    assert!(nodes.mark_up(&node1)?);
+    assert!(!nodes.mark_up(&node1)?);
+    let borrowed = nodes.try_borrow_nodes(&req, &any, 1, &[]).unwrap();
+    assert_eq!(borrowed[0].name(), "node1");
+
+    // prefer a node not avoided
+    nodes.return_nodes(&borrowed, &req)?;
+    nodes.return_nodes(&node2, &req)?;
+    let borrowed = nodes.try_borrow_nodes(&req, &any, 1, &[node1.clone()]).unwrap();
+    assert_eq!(borrowed[0].name(), "node2");
+    // but an avoided node is better than nothing
+    let borrowed = nodes.try_borrow_nodes(&req, &any, 1, &[node1]).unwrap();
+    assert_eq!(borrowed[0].name(), "node1");
+    assert_eq!(nodes.len(), 0);
+    assert!(nodes.try_borrow_nodes(&req, &any, 1, &[]).is_none());
+    Ok(())
+}
+
+#[test]
+fn test_nodes_slots() -> Result<()> {
+    let nodes = Nodes::new(["node1"]);
+    nodes.add_node(Node::from("node2").with_slots(2))?;
+    assert_eq!(nodes.len(), 3);
+
+    let borrowed = nodes.try_borrow_nodes(&Resources::default(), &Affinity::default(), 1, &[]).unwrap();
+    assert_eq!(borrowed[0].name(), "node1");
+    let node2: Node = "node2".into();
+    nodes.mark_down(&node2);
+    assert_eq!(nodes.len(), 0);
+    // all slots of node2 come back
+    nodes.mark_up(&node2)?;
+    assert_eq!(nodes.len(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_nodes_policy() -> Result<()> {
+    use crate::policy::Policy;
+
+    let req = Resources::default();
+    let any = Affinity::default();
+    // the node returned just now goes last
+    let nodes = Nodes::new(["node1", "node2"]);
+    let borrowed = nodes.try_borrow_nodes(&req, &any, 1, &[]).unwrap();
+    assert_eq!(borrowed[0].name(), "node1");
+    nodes.return_nodes(&borrowed, &req)?;
+    let borrowed = nodes.try_borrow_nodes(&req, &any, 1, &[]).unwrap();
+    assert_eq!(borrowed[0].name(), "node2");
+
+    let nodes = Nodes::new([Node::from("node1").with_slots(2), "node2".into()]).with_policy(Policy::LeastLoaded);
+    let borrowed = nodes.try_borrow_nodes(&req, &any, 2, &[]).unwrap();
+    assert_eq!(borrowed.iter().map(|x| x.name()).collect_vec(), ["node1", "node2"]);
+    // node1 is half loaded, and node2 is full
+    let borrowed = nodes.try_borrow_nodes(&req, &any, 1, &[]).unwrap();
+    assert_eq!(borrowed[0].name(), "node1");
+    Ok(())
+}
+
+#[test]
+fn test_nodes_retire_idle() -> Result<()> {
+    use std::time::Duration;
+
+    let req = Resources::default();
+    let any = Affinity::default();
+    let nodes = Nodes::new([
+        Node::from("node1").with_label("pool", "1"),
+        Node::from("node2").with_label("pool", "1"),
+        "node3".into(),
+    ]);
+    let elastic = |node: &Node| node.labels().contains_key("pool");
+    assert!(nodes.retire_idle(Duration::from_secs(60), elastic).is_empty());
+
+    let borrowed = nodes.try_borrow_nodes(&req, &any, 1, &[]).unwrap();
+    assert_eq!(borrowed[0].name(), "node1");
+    let retired = nodes.retire_idle(Duration::ZERO, elastic);
+    assert_eq!(retired.iter().map(|x| x.name()).collect_vec(), ["node2"]);
+    assert_eq!(nodes.registered().len(), 2);
+    nodes.return_nodes(&borrowed, &req)?;
+    assert_eq!(nodes.retire_idle(Duration::ZERO, elastic).len(), 1);
+    assert_eq!(nodes.registered().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_nodes_resources() -> Result<()> {
+    let small = Resources { cores: 4, memory: 8.0 };
+    let large = Resources { cores: 16, memory: 64.0 };
+    let any = Affinity::default();
+    let nodes = Nodes::new(Vec::<Node>::new());
+    // any job may fit nodes registered later
+    assert!(nodes.could_fit(&large, &any, 4));
+    nodes.add_node(Node::from("small").with_slots(4).with_capacity(small.clone()))?;
+    nodes.add_node(Node::from("large").with_slots(4).with_capacity(large.clone()))?;
+
+    // only the large node can fit
+    let req = Resources { cores: 8, memory: 4.0 };
+    assert!(nodes.could_fit(&req, &any, 1));
+    assert!(!nodes.could_fit(&req, &any, 2));
+    assert!(!nodes.could_fit(&Resources { cores: 32, memory: 1.0 }, &any, 1));
+    let mut fat = Affinity::default();
+    fat.selector.insert("partition".into(), "fat".into());
+    assert!(!nodes.could_fit(&Resources::default(), &fat, 1));
+    let borrowed = nodes.try_borrow_nodes(&req, &any, 1, &[]).unwrap();
+    assert_eq!(borrowed[0].name(), "large");
+    // two jobs fit on the small node at the same time
+    let req2 = Resources { cores: 2, memory: 4.0 };
+    let a = nodes.try_borrow_nodes(&req2, &any, 1, &[]).unwrap();
+    let b = nodes.try_borrow_nodes(&req2, &any, 1, &[]).unwrap();
+    assert_eq!(a[0].name(), "small");
+    assert_eq!(b[0].name(), "small");
+    // a multi-node job takes both nodes
+    let c = nodes.try_borrow_nodes(&Resources::default(), &any, 2, &[]).unwrap();
+    assert_eq!(c.len(), 2);
+    nodes.return_nodes(&a, &req2)?;
+    nodes.return_nodes(&b, &req2)?;
+    nodes.return_nodes(&borrowed, &req)?;
+
+    let job = Job::new("test").with_requires(req2.clone(), 2);
+    assert_eq!(job.nodes, 2);
+    assert!(req2.fits_in(&small));
+    assert!(!large.fits_in(&small));
+    Ok(())
+}
+
+#[test]
+fn test_nodes_affinity() -> Result<()> {
+    let nodes = Nodes::new(["plain"]);
+    nodes.add_node(Node::from("fat").with_label("partition", "fat").with_label("rack", "3"))?;
+    nodes.add_node(Node::from("thin").with_label("partition", "thin").with_label("rack", "3"))?;
+
+    let req = Resources::default();
+    let (k, v) = parse_label("partition = fat")?;
+    let mut fat = Affinity::default();
+    fat.selector.insert(k, v);
+    let borrowed = nodes.try_borrow_nodes(&req, &fat, 1, &[]).unwrap();
+    assert_eq!(borrowed[0].name(), "fat");
+
+    // not on rack 3
+    let mut away = Affinity::default();
+    away.anti_affinity.insert("rack".into(), "3".into());
+    let borrowed = nodes.try_borrow_nodes(&req, &away, 1, &[]).unwrap();
+    assert_eq!(borrowed[0].name(), "plain");
+
+    let mut thin = Affinity::default();
+    thin.selector.insert("rack".into(), "3".into());
+    thin.anti_affinity.insert("partition".into(), "fat".into());
+    let borrowed = nodes.try_borrow_nodes(&req, &thin, 1, &[]).unwrap();
+    assert_eq!(borrowed[0].name(), "thin");
+    assert!(parse_label("rack").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_retry_policy() {
+    let policy = RetryPolicy::default();
+    assert_eq!(policy.delay(1), None);
+
+    let policy = RetryPolicy {
+        max_attempts: 3,
+        backoff: 2.0,
+        ..Default::default()
+    };
+    assert_eq!(policy.delay(1), Some(2.0));
+    assert_eq!(policy.delay(2), Some(4.0));
+    assert_eq!(policy.delay(3), None);
+}
+
+#[tokio::test]
+async fn test_computation_usage() -> Result<()> {
+    let mut comput = Job::new("#! /bin/sh\necho done\nexit 3").submit()?;
+    assert!(comput.wait_for_output().await.is_err());
+    let usage = comput.usage().context("no usage")?;
+    assert_eq!(usage.exit_code, Some(3));
+    assert_eq!(usage.signal, None);
+    assert!(usage.max_rss > 0);
+
+    let mut comput = Job::new("#! /bin/sh\nsleep 10").with_timeout(0.2).submit()?;
+    assert!(comput.wait_for_output().await.is_err());
+    assert!(comput.is_timed_out());
+    let usage = comput.usage().context("no usage")?;
+    assert_eq!(usage.signal, Some(15));
+    assert!(usage.wall_time < 5.0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_computation_dropped() -> Result<()> {
+    use std::time::Duration;
+
+    let mut comput = Job::new("#! /bin/sh\ntrap '' TERM\necho $$\nsleep 30").submit()?;
+    // the request for the job dropped while it is running
+    let ret = tokio::time::timeout(Duration::from_secs_f64(0.5), comput.wait_for_output()).await;
+    assert!(ret.is_err());
+    let (stdout, _) = comput.read_output()?;
+    let pid: u32 = stdout.trim().parse()?;
+    drop(comput);
+
+    // killed even if SIGTERM ignored, and may not be reaped yet
+    let stat = format!("/proc/{pid}/stat");
+    let alive = || std::fs::read_to_string(&stat).is_ok_and(|s| !s.contains(") Z "));
+    for _ in 0..20 {
+        if !alive() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_secs_f64(0.1)).await;
+    }
+    assert!(!alive());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_computation_output() -> Result<()> {
+    use std::time::Duration;
+
+    // more than a pipe can hold written to stderr before stdout
+    let script = "#! /bin/sh\nhead -c 1000000 /dev/zero >&2\necho done";
+    let mut comput = Job::new(script).submit()?;
+    let out = tokio::time::timeout(Duration::from_secs(10), comput.wait_for_output()).await??;
+    assert_eq!(out, "done\n");
+    let (_, stderr) = comput.read_output()?;
+    assert_eq!(stderr.len(), 1000000);
+    Ok(())
+}
 // f725ca9b ends here
@@ -25,7 +25,7 @@ fn read_scheduler_address_from_lock_file(scheduler_address_file: &Path, timeout:
 #[derive(StructOpt)]
 struct ClientCli {
     /// The remote execution service address, e.g. localhost:3031
-    #[structopt(long = "address", conflicts_with = "scheduler-address-file")]
+    #[structopt(long = "address", conflicts_with = "scheduler_address_file")]
     scheduler_address: Option<String>,
 
     /// The scheduler address to be read from file `scheduler_address_file`
@@ -39,10 +39,96 @@ struct ClientCli {
 #[derive(Subcommand)]
 enum ClientAction {
     Run(ClientRun),
+    Submit(ClientSubmit),
+    Array(ClientArray),
+    /// Wait for job `id` to complete and print its result.
+    Result {
+        /// The job ID returned from submission.
+        id: usize,
+
+        /// Treat `id` as an array ID, and print results of all its tasks,
+        /// one per line.
+        #[arg(long)]
+        array: bool,
+    },
+    /// Print standard output of job `id`, read from the remote node while
+    /// it is running.
+    Logs {
+        /// The job ID returned from submission.
+        id: usize,
+
+        /// Wait for the job to start, and keep printing new output until
+        /// it finished.
+        #[arg(short, long)]
+        follow: bool,
+    },
+    /// Cancel job `id`, terminating its processes on remote node.
+    Cancel {
+        /// The job ID returned from submission.
+        id: usize,
+    },
+    /// Show status of nodes and jobs in the scheduler.
+    Status {
+        /// Print in JSON format
+        #[arg(long)]
+        json: bool,
+    },
+    /// List jobs pending in queue in dispatching order.
+    Queue,
+    /// Watch lifecycle events of jobs and nodes live, until the scheduler
+    /// shut down.
+    Watch {
+        /// Print each event in JSON format
+        #[arg(long)]
+        json: bool,
+    },
+    /// Reorder pending job `id` in queue.
+    Reorder {
+        /// The job ID returned from submission.
+        id: usize,
+
+        /// Change the priority of the job.
+        #[arg(long, allow_hyphen_values = true, conflicts_with = "position", required_unless_present = "position")]
+        priority: Option<i32>,
+
+        /// Move the job to position in queue, 0 for the next to dispatch.
+        #[arg(long)]
+        position: Option<usize>,
+    },
     /// Request server to add a new node for remote computation.
     AddNode {
         /// The node to be added into node list for remote computation.
         node: String,
+
+        /// The number of jobs can be run on the node at the same time,
+        /// or "auto" for the number of CPU cores on this machine.
+        #[arg(long, default_value = "1", value_parser = parse_slots)]
+        slots: usize,
+
+        /// The number of CPU cores provided by the node. By default, the
+        /// node is limited by its slots only.
+        #[arg(long, requires = "memory")]
+        cores: Option<usize>,
+
+        /// The size of memory in GiB provided by the node.
+        #[arg(long, requires = "cores")]
+        memory: Option<f64>,
+
+        /// Add label `key=value` to the node, e.g. partition=fat. Can be
+        /// repeated.
+        #[arg(long = "label", value_parser = base::parse_label)]
+        labels: Vec<(String, String)>,
+    },
+    /// Shut down the scheduler, cancelling all pending jobs.
+    Shutdown {
+        /// Also shut down all registered workers.
+        #[arg(long)]
+        workers: bool,
+
+        /// The time in seconds to wait for running jobs to finish. By
+        /// default, running jobs will be cancelled immediately.
+        #[arg(long)]
+        wait: Option<f64>,
     },
     /// Request server to compute molecule from `mol_path`
     Compute {
@@ -51,7 +137,8 @@ enum ClientAction {
 }
 
 #[derive(StructOpt)]
-/// request server to run a cmd
+/// request server to run a cmd, printing its stdout and exiting with its
+/// exit code
 struct ClientRun {
     /// The cmd to run in remote session
     cmd: String,
@@ -59,6 +146,210 @@ struct ClientRun {
     /// The working dir to run the cmd
     #[structopt(long, default_value = ".")]
     wrk_dir: PathBuf,
+
+    /// The wall-time limit in seconds for running the cmd
+    #[structopt(long)]
+    timeout: Option<f64>,
+
+    /// The maximum number of attempts to run the cmd. By default, only
+    /// transport errors with remote node will be retried.
+    #[structopt(long, default_value = "1")]
+    max_attempts: usize,
+
+    /// Also retry when the cmd exited unsuccessfully
+    #[structopt(long)]
+    retry_on_failure: bool,
+
+    /// The delay in seconds before the first retry, doubled for each
+    /// subsequent retry
+    #[structopt(long, default_value = "1.0")]
+    retry_backoff: f64,
+
+    /// The number of CPU cores required on each node
+    #[structopt(long, default_value = "0")]
+    cores: usize,
+
+    /// The size of memory in GiB required on each node
+    #[structopt(long, default_value = "0")]
+    memory: f64,
+
+    /// The number of nodes required. The cmd will be run on the first
+    /// node, with all host names in environment variable GOSH_NODES.
+    #[structopt(long, default_value = "1", value_parser = clap::value_parser!(u64).range(1..))]
+    nodes: u64,
+
+    /// Only run on nodes with label `key=value`. Can be repeated.
+    #[structopt(long = "select", value_parser = base::parse_label)]
+    selector: Vec<(String, String)>,
+
+    /// Never run on nodes with label `key=value`. Can be repeated.
+    #[structopt(long, value_parser = base::parse_label)]
+    anti_affinity: Vec<(String, String)>,
+
+    /// The priority for dispatching the cmd. Jobs with higher priority
+    /// will be dispatched first.
+    #[structopt(long, default_value = "0", allow_hyphen_values = true)]
+    priority: i32,
+
+    /// Hold the cmd until job `ID` completed successfully. Can be
+    /// repeated. The cmd will be cancelled if any of them failed.
+    #[structopt(long = "after", value_name = "ID")]
+    depends_on: Vec<usize>,
+
+    /// Run the cmd once all jobs in `--after` finished, even if some of
+    /// them failed
+    #[structopt(long, requires = "depends_on")]
+    always_run: bool,
+
+    /// The input file read by the cmd, relative to the working dir. Can
+    /// be repeated. The result may be reused from cache for the same cmd
+    /// with unchanged input files, if caching enabled on the scheduler.
+    #[structopt(long = "input", value_name = "FILE")]
+    inputs: Vec<PathBuf>,
+
+    /// Always run the cmd instead of reusing a cached result
+    #[structopt(long)]
+    no_cache: bool,
+}
+
+impl ClientRun {
+    /// Construct the job for running the cmd.
+    fn to_job(&self) -> Result<base::Job> {
+        let wrk_dir = self.wrk_dir.canonicalize()?;
+        let retry = base::RetryPolicy {
+            max_attempts: self.max_attempts,
+            on_failure: self.retry_on_failure,
+            backoff: self.retry_backoff,
+        };
+        let requires = base::Resources {
+            cores: self.cores,
+            memory: self.memory,
+        };
+        let affinity = base::Affinity {
+            selector: self.selector.iter().cloned().collect(),
+            anti_affinity: self.anti_affinity.iter().cloned().collect(),
+        };
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|p| {
+                let p = wrk_dir.join(p);
+                p.canonicalize().with_context(|| format!("invalid input file {p:?}"))
+            })
+            .collect::<Result<_>>()?;
+        let mut job = base::Job::from_cmd(&self.cmd, &wrk_dir)
+            .with_retry(retry)
+            .with_requires(requires, self.nodes as usize)
+            .with_affinity(affinity)
+            .with_priority(self.priority)
+            .with_dependencies(self.depends_on.clone(), self.always_run)
+            .with_inputs(inputs)
+            .with_no_cache(self.no_cache);
+        if let Some(t) = self.timeout {
+            job = job.with_timeout(t);
+        }
+        Ok(job)
+    }
+}
+
+/// Print `status` of the scheduler in tables.
+fn print_scheduler_status(status: &crate::SchedulerStatus) {
+    println!(
+        "uptime: {:.0}s, queued: {}, running: {}, finished: {}",
+        status.uptime, status.queued, status.running, status.finished
+    );
+    println!();
+    println!("{:<24} {:<6} {:>7} {:>6} {:>9}  JOBS", "NODE", "STATE", "SLOTS", "CORES", "MEMORY");
+    for x in &status.nodes {
+        let slots = format!("{}/{}", x.running, x.node.slots());
+        let (cores, memory) = match x.node.capacity() {
+            Some(c) => (format!("{}/{}", x.used.cores, c.cores), format!("{:.1}/{:.1}", x.used.memory, c.memory)),
+            None => ("-".into(), "-".into()),
+        };
+        let state = format!("{:?}", x.state);
+        let jobs = x.jobs.iter().join(",");
+        println!("{:<24} {:<6} {:>7} {:>6} {:>9}  {jobs}", x.node.name(), state, slots, cores, memory);
+    }
+    println!();
+    println!("{:>6} {:<8} {:<24} {:>8} {:>8}  NAME", "ID", "STATE", "NODE", "AGE", "ELAPSED");
+    for j in &status.jobs {
+        let state = format!("{:?}", j.state);
+        let node = j.node.as_deref().unwrap_or("-");
+        let age = format!("{:.0}s", j.age);
+        let elapsed = j.elapsed.map(|t| format!("{t:.0}s")).unwrap_or("-".into());
+        println!("{:>6} {:<8} {:<24} {:>8} {:>8}  {}", j.id, state, node, age, elapsed, j.name);
+    }
+}
+
+#[derive(StructOpt)]
+/// request server to run a cmd in background
+struct ClientSubmit {
+    #[clap(flatten)]
+    run: ClientRun,
+
+    /// Print the job ID and exit without waiting for the job to complete
+    #[structopt(long)]
+    detach: bool,
+}
+
+#[derive(StructOpt)]
+/// request server to run a cmd as array job, with one task for each index
+/// or parameter set. The task index is available to the cmd in environment
+/// variable GOSH_ARRAY_INDEX.
+struct ClientArray {
+    #[clap(flatten)]
+    run: ClientRun,
+
+    /// Run one task for each index in range `START..END` (END excluded),
+    /// e.g. 0..10
+    #[structopt(long, value_parser = parse_range, conflicts_with = "params", required_unless_present = "params")]
+    range: Option<std::ops::Range<usize>>,
+
+    /// Run one task for each parameter set read from `file`, one set per
+    /// line as `key=value` pairs separated by spaces. The parameters are
+    /// available to the cmd as environment variables.
+    #[structopt(long, value_name = "FILE")]
+    params: Option<PathBuf>,
+
+    /// Print the array ID and exit without waiting for all tasks to
+    /// complete
+    #[structopt(long)]
+    detach: bool,
+}
+
+impl ClientArray {
+    /// Construct the array job for running the cmd.
+    fn to_array_job(&self) -> Result<crate::ArrayJob> {
+        use crate::ArrayTasks;
+
+        let tasks = match (&self.range, &self.params) {
+            (Some(range), _) => ArrayTasks::Range {
+                start: range.start,
+                end: range.end,
+            },
+            (None, Some(file)) => ArrayTasks::Params(read_params(file)?),
+            (None, None) => bail!("either --range or --params should be specified"),
+        };
+        let job = self.run.to_job()?;
+        Ok(crate::ArrayJob { job, tasks })
+    }
+}
+
+/// Parse index range in `START..END` form.
+fn parse_range(s: &str) -> Result<std::ops::Range<usize>> {
+    let (start, end) = s.split_once("..").ok_or(format_err!("invalid range {s:?}, expect START..END"))?;
+    Ok(start.trim().parse()?..end.trim().parse()?)
+}
+
+/// Read parameter sets from `file`, one set per line as `key=value` pairs.
+/// Empty lines and lines starting with `#` are ignored.
+fn read_params(file: &Path) -> Result<Vec<std::collections::BTreeMap<String, String>>> {
+    let txt = read_file(file)?;
+    txt.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.split_whitespace().map(base::parse_label).collect())
+        .collect()
 }
 
 impl ClientCli {
@@ -73,12 +364,101 @@ impl ClientCli {
         let client = Client::connect(&scheduler_address);
         match self.action {
             ClientAction::Run(run) => {
-                let wrk_dir = run.wrk_dir.canonicalize()?;
-                let o = client.run_cmd(&run.cmd, &wrk_dir).await?;
-                println!("{o}");
+                let id = client.submit_job(run.to_job()?).await?;
+                let r = client.wait_for_job_result(id).await?;
+                // behave like the cmd run locally
+                print!("{}", r.stdout);
+                std::io::stdout().flush()?;
+                if let Some(err) = &r.error {
+                    eprint!("{}", r.stderr);
+                    eprintln!("job {id} {:?}: {}", err.kind, err.message);
+                }
+                let code = r.exit_status();
+                if code != 0 {
+                    std::process::exit(code);
+                }
+            }
+            ClientAction::Submit(submit) => {
+                let id = client.submit_job(submit.run.to_job()?).await?;
+                if submit.detach {
+                    println!("{id}");
+                } else {
+                    let r = client.wait_for_job_result(id).await?;
+                    println!("{}", serde_json::to_string(&r)?);
+                }
             }
-            ClientAction::AddNode { node } => {
-                client.add_node(&node).await?;
+            ClientAction::Array(array) => {
+                let id = client.submit_array(array.to_array_job()?).await?;
+                if array.detach {
+                    println!("{id}");
+                } else {
+                    for r in client.wait_for_array_result(id).await? {
+                        println!("{}", serde_json::to_string(&r)?);
+                    }
+                }
+            }
+            ClientAction::Result { id, array: false } => {
+                let r = client.wait_for_job_result(id).await?;
+                println!("{}", serde_json::to_string(&r)?);
+            }
+            ClientAction::Result { id, array: true } => {
+                for r in client.wait_for_array_result(id).await? {
+                    println!("{}", serde_json::to_string(&r)?);
+                }
+            }
+            ClientAction::Logs { id, follow } => {
+                client.copy_job_output(id, follow, std::io::stdout()).await?;
+            }
+            ClientAction::Cancel { id } => {
+                client.cancel_job(id).await?;
+            }
+            ClientAction::Status { json } => {
+                let status = client.scheduler_status().await?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&status)?);
+                } else {
+                    print_scheduler_status(&status);
+                }
+            }
+            ClientAction::Queue => {
+                println!("POSITION       ID PRIORITY  NAME");
+                for job in client.queued_jobs().await? {
+                    let id = job.id.map(|x| x.to_string()).unwrap_or("-".into());
+                    println!("{:>8} {:>8} {:>8}  {}", job.position, id, job.priority, job.name);
+                }
+            }
+            ClientAction::Watch { json } => {
+                let mut events = client.watch_events().await?;
+                while let Some(event) = events.next().await? {
+                    if json {
+                        println!("{}", serde_json::to_string(&event)?);
+                    } else {
+                        println!("{event}");
+                    }
+                }
+            }
+            ClientAction::Reorder { id, priority, position } => {
+                let update = crate::QueueUpdate { priority, position };
+                client.reorder_job(id, update).await?;
+            }
+            ClientAction::AddNode {
+                node,
+                slots,
+                cores,
+                memory,
+                labels,
+            } => {
+                let mut node = Node::from(node).with_slots(slots);
+                if let (Some(cores), Some(memory)) = (cores, memory) {
+                    node = node.with_capacity(base::Resources { cores, memory });
+                }
+                for (key, value) in labels {
+                    node = node.with_label(&key, &value);
+                }
+                client.add_node(node).await?;
+            }
+            ClientAction::Shutdown { workers, wait } => {
+                client.shutdown_scheduler(wait, workers).await?;
             }
 
             ClientAction::Compute { mol_path } => {
@@ -97,7 +477,7 @@ impl ClientCli {
 // 512e88e7 ends here
 
 // [[file:../remote.note::674c2404][674c2404]]
-use base::LockFile;
+use base::{LockFile, Node};
 use server::Server;
 
 #[derive(Debug, Clone, ValueEnum)]
@@ -106,6 +486,110 @@ enum ServerMode {
     AsWorker,
 }
 
+/// The options for scheduler only
+#[derive(Args, Debug, Clone, Default)]
+struct SchedulerArgs {
+    /// The policy for choosing nodes to run jobs.
+    #[arg(long, value_enum, default_value_t)]
+    policy: crate::Policy,
+
+    /// The time in seconds a job waits for the node which ran the last
+    /// job in the same working directory, before running on other
+    /// nodes. The node is always preferred when free.
+    #[arg(long, default_value = "0")]
+    locality_wait: f64,
+
+    /// The time in seconds a job can be blocked by lack of free nodes
+    /// while jobs after it in queue go first. Then nodes becoming free
+    /// will be kept for it. Unlimited if zero.
+    #[arg(long, default_value = "600")]
+    backfill_wait: f64,
+
+    /// The maximum number of completed results cached for reusing by jobs
+    /// with the same cmd, working dir and input files. Caching is
+    /// disabled if zero.
+    #[arg(long, default_value = "0")]
+    cache_size: usize,
+
+    /// The time in seconds a cached result can be reused.
+    #[arg(long, default_value = "86400")]
+    cache_age: f64,
+
+    /// The maximum number of jobs pending in queue. New jobs will be
+    /// rejected until the queue drains. Unlimited if zero.
+    #[arg(long, default_value = "100000")]
+    max_queued: usize,
+
+    /// The maximum number of unfinished jobs submitted from each client
+    /// host. Unlimited if zero.
+    #[arg(long, default_value = "0")]
+    max_client_jobs: usize,
+
+    /// The maximum number of finished jobs kept for querying their status
+    /// and results. Unlimited if zero.
+    #[arg(long, default_value = "100000")]
+    keep_finished: usize,
+
+    /// The time in seconds a finished job is kept. Unlimited if zero.
+    #[arg(long, default_value = "86400")]
+    keep_age: f64,
+
+    /// Launch workers on demand with this command template, run with `sh
+    /// -c`. `{host}`, `{scheduler}` and `{id}` will be replaced with the
+    /// host to launch on, the scheduler address and the launch ID, e.g.
+    /// "ssh {host} gosh-remote bootstrap --scheduler {scheduler} --label
+    /// gosh.elastic={id} as-worker"
+    #[arg(long, value_name = "TEMPLATE")]
+    launch: Option<String>,
+
+    /// The host to launch workers on in turn. Can be repeated. Default to
+    /// localhost.
+    #[arg(long = "launch-host", value_name = "HOST", requires = "launch")]
+    launch_hosts: Vec<String>,
+
+    /// The maximum number of workers launched at the same time
+    #[arg(long, default_value = "4")]
+    max_launched: usize,
+
+    /// Launch a new worker when more jobs than this in queue are ready
+    /// but cannot fit in available nodes
+    #[arg(long, default_value = "0")]
+    scale_up: usize,
+
+    /// Retire a launched worker when idle for this number of seconds
+    #[arg(long, default_value = "60")]
+    idle_timeout: f64,
+
+    /// The scheduler address for launched workers to register with.
+    /// Default to the address the scheduler listening on.
+    #[arg(long, value_name = "ADDRESS", requires = "launch")]
+    advertise: Option<String>,
+}
+
+impl SchedulerArgs {
+    fn config(&self) -> crate::SchedulerConfig {
+        crate::SchedulerConfig {
+            policy: self.policy,
+            locality_wait: self.locality_wait,
+            backfill_wait: self.backfill_wait,
+            cache_size: self.cache_size,
+            cache_age: self.cache_age,
+            max_queued: self.max_queued,
+            max_client_jobs: self.max_client_jobs,
+            keep_finished: self.keep_finished,
+            keep_age: self.keep_age,
+            pool: self.launch.as_ref().map(|launch| crate::PoolConfig {
+                launch: launch.clone(),
+                hosts: self.launch_hosts.clone(),
+                max_workers: self.max_launched,
+                scale_up: self.scale_up,
+                idle_timeout: self.idle_timeout,
+                advertise: self.advertise.clone(),
+            }),
+        }
+    }
+}
+
 /// The server side for running program concurrently distributed over multiple remote nodes
 #[derive(Parser, Debug)]
 struct ServerCli {
@@ -122,6 +606,9 @@ struct ServerCli {
     /// run simple command line.
     #[arg(short = 't')]
     bbm_dir: Option<PathBuf>,
+
+    #[command(flatten)]
+    scheduler: SchedulerArgs,
 }
 
 impl ServerCli {
@@ -131,7 +618,7 @@ impl ServerCli {
         match self.mode {
             ServerMode::AsScheduler => {
                 println!("Start scheduler serivce at {address:?}");
-                server.serve_as_scheduler().await;
+                server.serve_as_scheduler(self.scheduler.config()).await?;
             }
             ServerMode::AsWorker => {
                 if let Some(bbm_dir) = self.bbm_dir {
@@ -149,11 +636,12 @@ impl ServerCli {
         Ok(())
     }
 
-    async fn run_as_scheduler(address: String) -> Result<()> {
+    async fn run_as_scheduler(address: String, scheduler: SchedulerArgs) -> Result<()> {
         let server = ServerCli {
             address: address,
             mode: ServerMode::AsScheduler,
             bbm_dir: None,
+            scheduler,
         };
         server.enter_main().await?;
         Ok(())
@@ -164,6 +652,7 @@ impl ServerCli {
             address: address,
             mode: ServerMode::AsWorker,
             bbm_dir: None,
+            scheduler: Default::default(),
         };
         server.enter_main().await?;
         Ok(())
@@ -174,6 +663,7 @@ impl ServerCli {
             address: address,
             mode: ServerMode::AsWorker,
             bbm_dir: bbm_dir.into(),
+            scheduler: Default::default(),
         };
         server.enter_main().await?;
         Ok(())
@@ -198,11 +688,42 @@ struct BootstrapCli {
     #[arg(short = 't')]
     bbm_dir: Option<PathBuf>,
 
+    /// The number of jobs the worker can run at the same time, or "auto"
+    /// for the number of CPU cores detected.
+    #[arg(long, default_value = "1", value_parser = parse_slots)]
+    slots: usize,
+
+    /// The number of CPU cores the worker provides for jobs. Detected
+    /// automatically by default.
+    #[arg(long)]
+    cores: Option<usize>,
+
+    /// The size of memory in GiB the worker provides for jobs. Detected
+    /// automatically by default.
+    #[arg(long)]
+    memory: Option<f64>,
+
+    /// Register the worker with label `key=value`, e.g. partition=fat.
+    /// Can be repeated.
+    #[arg(long = "label", value_parser = base::parse_label)]
+    labels: Vec<(String, String)>,
+
+    /// Register the worker with the scheduler at this address, instead of
+    /// reading it from `address_file`.
+    #[arg(long = "scheduler", value_name = "ADDRESS", env = crate::scheduler::SCHEDULER_VAR)]
+    scheduler_address: Option<String>,
+
+    #[command(flatten)]
+    scheduler: SchedulerArgs,
+
     /// The server mode to start.
     #[arg(value_enum)]
     mode: ServerMode,
 }
 
+/// The time in seconds to wait for the worker to be ready for jobs
+const WORKER_READY_TIMEOUT: f64 = 10.0;
+
 impl BootstrapCli {
     async fn enter_main(&self) -> Result<()> {
         let node = hostname();
@@ -214,22 +735,67 @@ impl BootstrapCli {
             ServerMode::AsScheduler => {
                 info!("install scheduler on {node}");
                 let _lock = LockFile::new(&address_file, &address)?;
-                ServerCli::run_as_scheduler(address).await?;
+                ServerCli::run_as_scheduler(address, self.scheduler.clone()).await?;
             }
             ServerMode::AsWorker => {
                 info!("install worker on {node}");
-                let o = read_scheduler_address_from_lock_file(&address_file, timeout)?;
+                let o = match &self.scheduler_address {
+                    Some(o) => o.clone(),
+                    None => read_scheduler_address_from_lock_file(&address_file, timeout)?,
+                };
                 // tell the scheduler add this worker
-                crate::Client::connect(o).add_node(&address).await?;
-                if let Some(bbm_dir) = bbm_dir {
-                    ServerCli::run_as_model(address, bbm_dir).await?;
-                } else {
-                    ServerCli::run_as_worker(address).await?;
+                let mut node = Node::from(&address)
+                    .with_slots(self.slots)
+                    .with_capacity(self.capacity()?);
+                for (key, value) in &self.labels {
+                    node = node.with_label(key, value);
+                }
+                // mark the worker launched by the elastic pool
+                if let Ok(id) = std::env::var(crate::scheduler::ELASTIC_ID_VAR) {
+                    node = node.with_label(crate::scheduler::ELASTIC_LABEL, &id);
                 }
+                let worker = {
+                    let address = address.clone();
+                    tokio::spawn(async move {
+                        if let Some(bbm_dir) = bbm_dir {
+                            ServerCli::run_as_model(address, bbm_dir).await
+                        } else {
+                            ServerCli::run_as_worker(address).await
+                        }
+                    })
+                };
+                // register only when ready, as jobs may be dispatched at once
+                crate::Client::connect(&address).wait_until_alive(WORKER_READY_TIMEOUT).await?;
+                crate::Client::connect(o).add_node(node).await?;
+                worker.await??;
             }
         }
         Ok(())
     }
+
+    /// Return the resources provided by the worker, overridden by command
+    /// line arguments.
+    fn capacity(&self) -> Result<base::Resources> {
+        let mut capacity = base::Resources::detect()?;
+        if let Some(cores) = self.cores {
+            capacity.cores = cores;
+        }
+        if let Some(memory) = self.memory {
+            capacity.memory = memory;
+        }
+        Ok(capacity)
+    }
+}
+
+/// Parse the number of job slots, "auto" for the number of CPU cores.
+fn parse_slots(s: &str) -> Result<usize> {
+    let n = if s == "auto" {
+        std::thread::available_parallelism()?.get()
+    } else {
+        s.parse()?
+    };
+    ensure!(n > 0, "slots must be positive");
+    Ok(n)
 }
 
 fn default_server_address() -> String {
@@ -23,13 +23,102 @@ impl Client {
 // d2c8de54 ends here
 
 // [[file:../remote.note::743b32f9][743b32f9]]
+use reqwest::{RequestBuilder, Response, StatusCode};
+
+/// Return the delay in seconds asked by the server before retrying, if
+/// the request was rejected as the server is overloaded.
+fn retry_after(resp: &Response) -> Option<u64> {
+    let status = resp.status();
+    if status != StatusCode::TOO_MANY_REQUESTS && status != StatusCode::SERVICE_UNAVAILABLE {
+        return None;
+    }
+    let value = resp.headers().get(reqwest::header::RETRY_AFTER)?;
+    value.to_str().ok()?.parse().ok()
+}
+
+/// The maximum number of times to send a request rejected by the
+/// overloaded server
+const MAX_ATTEMPTS: usize = 100;
+
 impl Client {
+    /// Send the request built by `build`. The request will be sent again
+    /// after the delay asked by the server when it is overloaded, up to
+    /// `MAX_ATTEMPTS` times in total.
+    async fn send(&self, build: impl Fn() -> RequestBuilder) -> Result<Response> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let resp = build().send().await?;
+            let Some(delay) = retry_after(&resp) else {
+                return Ok(resp);
+            };
+            let status = resp.status();
+            let text = resp.text().await.unwrap_or_default();
+            ensure!(
+                attempt < MAX_ATTEMPTS,
+                "request rejected with {status} for {attempt} times, giving up: {text}"
+            );
+            warn!("request rejected with {status}: {text}; retry in {delay}s");
+            tokio::time::sleep(std::time::Duration::from_secs(delay)).await;
+        }
+    }
+
     /// Apply Post request
     pub(crate) async fn post(&self, end_point: &str, data: impl serde::Serialize) -> Result<String> {
         trace!("post to {end_point:?}");
         let uri = format!("{}/{end_point}", self.service_uri);
-        let resp = self.client.post(&uri).json(&data).send().await?.text().await?;
+        let resp = self.send(|| self.client.post(&uri).json(&data)).await?;
+        let status = resp.status();
+        let text = resp.text().await?;
+        ensure!(status.is_success(), "{status}: {text}");
+        Ok(text)
+    }
+
+    /// Apply Get request
+    pub(crate) async fn get(&self, end_point: &str) -> Result<String> {
+        trace!("get from {end_point:?}");
+        let uri = format!("{}/{end_point}", self.service_uri);
+        let resp = self.send(|| self.client.get(&uri)).await?;
+        let status = resp.status();
+        let text = resp.text().await?;
+        ensure!(status.is_success(), "{status}: {text}");
+        Ok(text)
+    }
+
+    /// Apply Get request, and return the response for reading its body
+    /// as a stream.
+    pub(crate) async fn get_stream(&self, end_point: &str) -> Result<Response> {
+        trace!("get stream from {end_point:?}");
+        let uri = format!("{}/{end_point}", self.service_uri);
+        let resp = self.send(|| self.client.get(&uri)).await?;
+        let status = resp.status();
+        if !status.is_success() {
+            let text = resp.text().await?;
+            bail!("{status}: {text}");
+        }
         Ok(resp)
     }
+
+    /// Apply Put request
+    pub(crate) async fn put(&self, end_point: &str, data: impl serde::Serialize) -> Result<String> {
+        trace!("put to {end_point:?}");
+        let uri = format!("{}/{end_point}", self.service_uri);
+        let resp = self.send(|| self.client.put(&uri).json(&data)).await?;
+        let status = resp.status();
+        let text = resp.text().await?;
+        ensure!(status.is_success(), "{status}: {text}");
+        Ok(text)
+    }
+
+    /// Apply Delete request
+    pub(crate) async fn delete(&self, end_point: &str) -> Result<String> {
+        trace!("delete {end_point:?}");
+        let uri = format!("{}/{end_point}", self.service_uri);
+        let resp = self.send(|| self.client.delete(&uri)).await?;
+        let status = resp.status();
+        let text = resp.text().await?;
+        ensure!(status.is_success(), "{status}: {text}");
+        Ok(text)
+    }
 }
 // 743b32f9 ends here
@@ -12,6 +12,8 @@ use std::path::{Path, PathBuf};
 // [[file:../remote.note::b21b77b4][b21b77b4]]
 mod base;
 mod client;
+mod metrics;
+mod policy;
 mod rest;
 mod scheduler;
 mod server;
@@ -63,10 +65,16 @@ fn test_addr() {
 // 92bf67b7 ends here
 
 // [[file:../remote.note::0a725e9c][0a725e9c]]
-pub use base::LockFile;
+pub use base::{JobUsage, LockFile};
 
 pub use crate::client::Client;
+pub use crate::policy::{Candidate, Policy, SchedulingPolicy};
+pub use crate::scheduler::{ActiveJob, NodeState, NodeStatus, SchedulerStatus};
+pub use crate::scheduler::{ArrayId, ArrayJob, ArrayStatus, ArrayTaskResult, ArrayTaskStatus, ArrayTasks};
+pub use crate::scheduler::{Event, EventStream};
+pub use crate::scheduler::{JobId, JobState, JobStatus, PoolConfig, QueueUpdate, QueuedJob, SchedulerConfig};
 pub use crate::server::Server;
+pub use crate::worker::{ComputationResult, ErrorKind, JobError};
 pub use jobhub::JobHub;
 // 0a725e9c ends here
 
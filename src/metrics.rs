@@ -0,0 +1,276 @@
+// [[file:../remote.note::61348440][61348440]]
+//! Prometheus metrics for the scheduler and workers
+// 61348440 ends here
+
+// [[file:../remote.note::f0c49cbe][f0c49cbe]]
+#![deny(warnings)]
+
+use super::*;
+use crate::rest::AppError;
+use crate::scheduler::{JobState, NodeState, SchedulerStatus};
+
+use axum::http::header::{HeaderName, CONTENT_TYPE};
+use prometheus::{Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry};
+use std::sync::OnceLock;
+// f0c49cbe ends here
+
+// [[file:../remote.note::579a827f][579a827f]]
+/// Buckets in seconds for histograms of job timings, from 0.1 second to 1
+/// day.
+const TIME_BUCKETS: &[f64] = &[
+    0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 900.0, 1800.0, 3600.0, 7200.0, 21600.0, 86400.0,
+];
+
+fn counter(registry: &Registry, name: &str, help: &str) -> IntCounter {
+    let c = IntCounter::new(name, help).unwrap();
+    registry.register(Box::new(c.clone())).unwrap();
+    c
+}
+
+fn counter_vec(registry: &Registry, name: &str, help: &str, label: &str) -> IntCounterVec {
+    let c = IntCounterVec::new(Opts::new(name, help), &[label]).unwrap();
+    registry.register(Box::new(c.clone())).unwrap();
+    c
+}
+
+fn gauge(registry: &Registry, name: &str, help: &str) -> IntGauge {
+    let g = IntGauge::new(name, help).unwrap();
+    registry.register(Box::new(g.clone())).unwrap();
+    g
+}
+
+fn histogram(registry: &Registry, name: &str, help: &str) -> Histogram {
+    let opts = HistogramOpts::new(name, help).buckets(TIME_BUCKETS.to_vec());
+    let h = Histogram::with_opts(opts).unwrap();
+    registry.register(Box::new(h.clone())).unwrap();
+    h
+}
+
+/// Encode all metrics in `registry` in Prometheus text format.
+fn encode(registry: &Registry) -> Result<String> {
+    let mut buf = vec![];
+    prometheus::TextEncoder::new().encode(&registry.gather(), &mut buf)?;
+    Ok(String::from_utf8(buf)?)
+}
+
+/// Return the label for finished job `state`.
+fn state_label(state: JobState) -> &'static str {
+    match state {
+        JobState::Queued => "queued",
+        JobState::Running => "running",
+        JobState::Completed => "completed",
+        JobState::Failed => "failed",
+        JobState::Cancelled => "cancelled",
+        JobState::TimedOut => "timed_out",
+    }
+}
+
+/// Metrics collected by the scheduler
+pub(crate) struct SchedulerMetrics {
+    registry: Registry,
+    submitted: IntCounter,
+    finished: IntCounterVec,
+    retried: IntCounter,
+    requeued: IntCounter,
+    cache_hits: IntCounter,
+    queue_wait: Histogram,
+    runtime: Histogram,
+    queued: IntGauge,
+    running: IntGauge,
+    nodes: IntGaugeVec,
+}
+
+impl SchedulerMetrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+        let nodes = IntGaugeVec::new(Opts::new("gosh_scheduler_nodes", "Number of nodes by state"), &["state"]).unwrap();
+        registry.register(Box::new(nodes.clone())).unwrap();
+        Self {
+            submitted: counter(&registry, "gosh_scheduler_jobs_submitted_total", "Number of jobs submitted"),
+            finished: counter_vec(
+                &registry,
+                "gosh_scheduler_jobs_finished_total",
+                "Number of jobs finished by final state",
+                "state",
+            ),
+            retried: counter(&registry, "gosh_scheduler_jobs_retried_total", "Number of job retries after failures"),
+            requeued: counter(&registry, "gosh_scheduler_jobs_requeued_total", "Number of jobs requeued as node down"),
+            cache_hits: counter(&registry, "gosh_scheduler_cache_hits_total", "Number of jobs answered from cache"),
+            queue_wait: histogram(&registry, "gosh_scheduler_job_queue_wait_seconds", "Time jobs spent in queue"),
+            runtime: histogram(&registry, "gosh_scheduler_job_runtime_seconds", "Time jobs spent on remote nodes"),
+            queued: gauge(&registry, "gosh_scheduler_jobs_queued", "Number of jobs pending in queue"),
+            running: gauge(&registry, "gosh_scheduler_jobs_running", "Number of jobs running on remote nodes"),
+            nodes,
+            registry,
+        }
+    }
+
+    /// Record a job submitted.
+    pub fn job_submitted(&self) {
+        self.submitted.inc();
+    }
+
+    /// Record a job dispatched after waiting `wait` seconds in queue.
+    pub fn job_started(&self, wait: f64) {
+        self.queue_wait.observe(wait);
+    }
+
+    /// Record a job finished in `state`, after running `runtime` seconds
+    /// on remote node if dispatched.
+    pub fn job_finished(&self, state: JobState, runtime: Option<f64>) {
+        self.finished.with_label_values(&[state_label(state)]).inc();
+        if let Some(t) = runtime {
+            self.runtime.observe(t);
+        }
+    }
+
+    /// Record a job retried after failure.
+    pub fn job_retried(&self) {
+        self.retried.inc();
+    }
+
+    /// Record a job requeued as its node is down.
+    pub fn job_requeued(&self) {
+        self.requeued.inc();
+    }
+
+    /// Record a job answered with a cached result.
+    pub fn cache_hit(&self) {
+        self.cache_hits.inc();
+    }
+
+    /// Render all metrics in Prometheus text format, with gauges updated
+    /// from current `status` of the scheduler.
+    pub fn render(&self, status: &SchedulerStatus) -> Result<String> {
+        self.queued.set(status.queued as i64);
+        self.running.set(status.running as i64);
+        for (state, label) in [
+            (NodeState::Idle, "idle"),
+            (NodeState::Busy, "busy"),
+            (NodeState::Full, "full"),
+            (NodeState::Down, "down"),
+        ] {
+            let n = status.nodes.iter().filter(|x| x.state == state).count();
+            self.nodes.with_label_values(&[label]).set(n as i64);
+        }
+        encode(&self.registry)
+    }
+}
+
+/// Metrics collected by the worker
+pub(crate) struct WorkerMetrics {
+    registry: Registry,
+    jobs: IntCounterVec,
+    running: IntGauge,
+    runtime: Histogram,
+    molecules: IntCounterVec,
+}
+
+impl WorkerMetrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+        Self {
+            jobs: counter_vec(
+                &registry,
+                "gosh_worker_jobs_total",
+                "Number of jobs run on the worker by final state",
+                "state",
+            ),
+            running: gauge(&registry, "gosh_worker_jobs_running", "Number of jobs running on the worker"),
+            runtime: histogram(&registry, "gosh_worker_job_runtime_seconds", "Time jobs spent on the worker"),
+            molecules: counter_vec(
+                &registry,
+                "gosh_worker_molecules_computed_total",
+                "Number of molecule computations served by chemical model",
+                "result",
+            ),
+            registry,
+        }
+    }
+
+    /// Record a job started on the worker.
+    pub fn job_started(&self) {
+        self.running.inc();
+    }
+
+    /// Record a job finished in `state` after running `runtime` seconds.
+    pub fn job_finished(&self, state: JobState, runtime: f64) {
+        self.running.dec();
+        self.jobs.with_label_values(&[state_label(state)]).inc();
+        self.runtime.observe(runtime);
+    }
+
+    /// Record a molecule computation, successful or not.
+    pub fn molecule_computed(&self, ok: bool) {
+        let result = if ok { "ok" } else { "error" };
+        self.molecules.with_label_values(&[result]).inc();
+    }
+
+    /// Render all metrics in Prometheus text format.
+    pub fn render(&self) -> Result<String> {
+        encode(&self.registry)
+    }
+}
+
+/// Return metrics of the scheduler in this process.
+pub(crate) fn scheduler() -> &'static SchedulerMetrics {
+    static METRICS: OnceLock<SchedulerMetrics> = OnceLock::new();
+    METRICS.get_or_init(SchedulerMetrics::new)
+}
+
+/// Return metrics of the worker in this process.
+pub(crate) fn worker() -> &'static WorkerMetrics {
+    static METRICS: OnceLock<WorkerMetrics> = OnceLock::new();
+    METRICS.get_or_init(WorkerMetrics::new)
+}
+
+/// The content type of Prometheus text format
+const TEXT_FORMAT: &str = "text/plain; version=0.0.4";
+
+/// HTTP response of metrics in Prometheus text format
+pub(crate) type MetricsResponse = ([(HeaderName, &'static str); 1], String);
+
+/// Handle request for metrics of the worker
+pub(crate) async fn worker_metrics() -> Result<MetricsResponse, AppError> {
+    let body = worker().render()?;
+    Ok(([(CONTENT_TYPE, TEXT_FORMAT)], body))
+}
+
+/// Render metrics of the scheduler with current `status` as HTTP response.
+pub(crate) fn scheduler_metrics(status: &SchedulerStatus) -> Result<MetricsResponse, AppError> {
+    let body = scheduler().render(status)?;
+    Ok(([(CONTENT_TYPE, TEXT_FORMAT)], body))
+}
+// 579a827f ends here
+
+// [[file:../remote.note::004a27e7][004a27e7]]
+#[test]
+fn test_metrics() -> Result<()> {
+    let m = WorkerMetrics::new();
+    m.job_started();
+    m.job_finished(JobState::Completed, 1.5);
+    m.molecule_computed(false);
+    let txt = m.render()?;
+    assert!(txt.contains(r#"gosh_worker_jobs_total{state="completed"} 1"#));
+    assert!(txt.contains("gosh_worker_jobs_running 0"));
+    assert!(txt.contains(r#"gosh_worker_molecules_computed_total{result="error"} 1"#));
+
+    let m = SchedulerMetrics::new();
+    m.job_submitted();
+    m.job_finished(JobState::Failed, None);
+    let status = SchedulerStatus {
+        uptime: 1.0,
+        queued: 2,
+        running: 0,
+        finished: 1,
+        nodes: vec![],
+        jobs: vec![],
+    };
+    let txt = m.render(&status)?;
+    assert!(txt.contains("gosh_scheduler_jobs_submitted_total 1"));
+    assert!(txt.contains(r#"gosh_scheduler_jobs_finished_total{state="failed"} 1"#));
+    assert!(txt.contains("gosh_scheduler_jobs_queued 2"));
+    assert!(txt.contains(r#"gosh_scheduler_nodes{state="idle"} 0"#));
+    Ok(())
+}
+// 004a27e7 ends here
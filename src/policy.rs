@@ -0,0 +1,181 @@
+// [[file:../remote.note::29b06fb2][29b06fb2]]
+//! Policies for choosing remote nodes to run jobs
+// 29b06fb2 ends here
+
+// [[file:../remote.note::fe1259c2][fe1259c2]]
+#![deny(warnings)]
+
+use super::*;
+
+use std::sync::Mutex;
+use std::time::Instant;
+// fe1259c2 ends here
+
+// [[file:../remote.note::e7a31f5b][e7a31f5b]]
+/// A registered node which can fit the job to be placed
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    /// The position of the node in registration order
+    pub index: usize,
+    /// The number of jobs can be run on the node at the same time
+    pub slots: usize,
+    /// The number of jobs running on the node
+    pub running: usize,
+    /// The last time a slot of the node became free
+    pub released: Instant,
+}
+
+impl Candidate {
+    /// Compare the load of two candidates by the fraction of slots
+    /// occupied.
+    fn cmp_load(&self, other: &Self) -> std::cmp::Ordering {
+        (self.running * other.slots).cmp(&(other.running * self.slots))
+    }
+}
+
+/// The policy for choosing nodes among candidates which can fit a job
+pub trait SchedulingPolicy: Send + Sync {
+    /// Choose `n` nodes from `candidates`, which has at least `n` items.
+    /// Return the positions in registration order of chosen nodes.
+    fn choose(&self, candidates: &[Candidate], n: usize) -> Vec<usize>;
+}
+
+/// Choose the nodes that have been free for the longest time first, the
+/// same as nodes waiting in a queue.
+#[derive(Debug, Default)]
+pub struct Fifo;
+
+impl SchedulingPolicy for Fifo {
+    fn choose(&self, candidates: &[Candidate], n: usize) -> Vec<usize> {
+        candidates
+            .iter()
+            .sorted_by_key(|c| (c.released, c.index))
+            .take(n)
+            .map(|c| c.index)
+            .collect()
+    }
+}
+
+/// Choose nodes in turn by registration order, starting after the node
+/// chosen last time.
+#[derive(Debug, Default)]
+pub struct RoundRobin {
+    // the position in registration order to start with next time
+    next: Mutex<usize>,
+}
+
+impl SchedulingPolicy for RoundRobin {
+    fn choose(&self, candidates: &[Candidate], n: usize) -> Vec<usize> {
+        let mut next = self.next.lock().unwrap();
+        let start = *next;
+        let chosen = candidates
+            .iter()
+            .map(|c| c.index)
+            .sorted_by_key(|&i| (i < start, i))
+            .take(n)
+            .collect_vec();
+        if let Some(last) = chosen.last() {
+            *next = last + 1;
+        }
+        chosen
+    }
+}
+
+/// Choose the nodes with the smallest fraction of slots occupied first.
+#[derive(Debug, Default)]
+pub struct LeastLoaded;
+
+impl SchedulingPolicy for LeastLoaded {
+    fn choose(&self, candidates: &[Candidate], n: usize) -> Vec<usize> {
+        candidates
+            .iter()
+            .sorted_by(|a, b| a.cmp_load(b).then(a.index.cmp(&b.index)))
+            .take(n)
+            .map(|c| c.index)
+            .collect()
+    }
+}
+
+/// Choose the less loaded one of two nodes picked at random, for each node
+/// required. This balances load nearly as well as least-loaded, without
+/// piling jobs on the same nodes when there are many ties.
+#[derive(Debug, Default)]
+pub struct RandomOfTwo;
+
+impl SchedulingPolicy for RandomOfTwo {
+    fn choose(&self, candidates: &[Candidate], n: usize) -> Vec<usize> {
+        use rand::seq::index::sample;
+
+        let mut rng = rand::thread_rng();
+        let mut remained = candidates.to_vec();
+        let mut chosen = vec![];
+        while chosen.len() < n && !remained.is_empty() {
+            let picked = sample(&mut rng, remained.len(), remained.len().min(2)).into_vec();
+            let i = picked
+                .into_iter()
+                .min_by(|&a, &b| remained[a].cmp_load(&remained[b]))
+                .unwrap();
+            chosen.push(remained.swap_remove(i).index);
+        }
+        chosen
+    }
+}
+
+/// The available scheduling policies, selectable from command line
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, Deserialize, Serialize)]
+pub enum Policy {
+    /// Nodes free for the longest time first
+    #[default]
+    Fifo,
+    /// Nodes in turn by registration order
+    RoundRobin,
+    /// Nodes with the smallest fraction of slots occupied first
+    LeastLoaded,
+    /// The less loaded of two random nodes
+    RandomOfTwo,
+}
+
+impl Policy {
+    /// Construct the scheduling policy.
+    pub fn build(self) -> Box<dyn SchedulingPolicy> {
+        match self {
+            Self::Fifo => Box::new(Fifo),
+            Self::RoundRobin => Box::new(RoundRobin::default()),
+            Self::LeastLoaded => Box::new(LeastLoaded),
+            Self::RandomOfTwo => Box::new(RandomOfTwo),
+        }
+    }
+}
+// e7a31f5b ends here
+
+// [[file:../remote.note::ccb12a86][ccb12a86]]
+#[test]
+fn test_scheduling_policy() {
+    use std::time::Duration;
+
+    let now = Instant::now();
+    let candidate = |index: usize, running: usize, released: u64| Candidate {
+        index,
+        slots: 4,
+        running,
+        released: now + Duration::from_secs(released),
+    };
+    let candidates = [candidate(0, 2, 3), candidate(1, 0, 2), candidate(3, 1, 1)];
+
+    assert_eq!(Fifo.choose(&candidates, 2), [3, 1]);
+    assert_eq!(LeastLoaded.choose(&candidates, 2), [1, 3]);
+
+    let rr = RoundRobin::default();
+    assert_eq!(rr.choose(&candidates, 1), [0]);
+    assert_eq!(rr.choose(&candidates, 2), [1, 3]);
+    assert_eq!(rr.choose(&candidates, 1), [0]);
+
+    // the most loaded node never wins a pair
+    for _ in 0..20 {
+        let chosen = RandomOfTwo.choose(&candidates, 2);
+        assert_eq!(chosen.len(), 2);
+        assert!(!chosen.contains(&0));
+    }
+    assert_eq!(RandomOfTwo.choose(&candidates[..1], 1), [0]);
+}
+// ccb12a86 ends here
@@ -27,10 +27,34 @@ pub async fn shutdown_signal() {
 }
 // 415dc72b ends here
 
+// [[file:../remote.note::9abf054c][9abf054c]]
+/// The error for requests rejected as the server is overloaded. The
+/// client should retry after `retry_after` seconds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Overloaded {
+    /// Too many jobs pending in queue, answered with 503
+    QueueFull { retry_after: u64 },
+    /// Too many unfinished jobs from the same client, answered with 429
+    TooManyJobs { retry_after: u64 },
+}
+
+impl std::fmt::Display for Overloaded {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::QueueFull { retry_after } => write!(f, "job queue is full, retry after {retry_after}s"),
+            Self::TooManyJobs { retry_after } => write!(f, "too many unfinished jobs, retry after {retry_after}s"),
+        }
+    }
+}
+
+impl std::error::Error for Overloaded {}
+// 9abf054c ends here
+
 // [[file:../remote.note::8be5152c][8be5152c]]
 mod app_error {
+    use super::Overloaded;
     use crate::common::Error;
-    use axum::http::StatusCode;
+    use axum::http::{header, StatusCode};
     use axum::response::{IntoResponse, Response};
 
     // Make our own error that wraps `anyhow::Error`.
@@ -48,6 +72,13 @@ mod app_error {
     // Tell axum how to convert `AppError` into a response.
     impl IntoResponse for AppError {
         fn into_response(self) -> Response {
+            if let Some(err) = self.0.downcast_ref::<Overloaded>() {
+                let (status, retry_after) = match err {
+                    Overloaded::QueueFull { retry_after } => (StatusCode::SERVICE_UNAVAILABLE, retry_after),
+                    Overloaded::TooManyJobs { retry_after } => (StatusCode::TOO_MANY_REQUESTS, retry_after),
+                };
+                return (status, [(header::RETRY_AFTER, retry_after.to_string())], err.to_string()).into_response();
+            }
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 format!("Something went wrong: {}", self.0),
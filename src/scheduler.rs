@@ -1,20 +1,38 @@
 // [[file:../remote.note::c07df478][c07df478]]
 use super::*;
 
-use base::{Job, Node};
+use base::{Affinity, Job, Node, Resources};
 // c07df478 ends here
 
 // [[file:../remote.note::b1a3ac5f][b1a3ac5f]]
+mod array;
+mod cache;
+mod limits;
+mod pool;
 mod dispatch;
+mod events;
+mod queue;
+mod registry;
+mod status;
+
+pub use array::{ArrayId, ArrayJob, ArrayStatus, ArrayTaskResult, ArrayTaskStatus, ArrayTasks};
+pub use events::{Event, EventStream};
+pub use pool::PoolConfig;
+pub(crate) use pool::{ELASTIC_ID_VAR, ELASTIC_LABEL, SCHEDULER_VAR};
+pub use queue::{QueueUpdate, QueuedJob};
+pub use registry::{JobId, JobState, JobStatus};
+pub use status::{ActiveJob, NodeState, NodeStatus, SchedulerStatus};
 // b1a3ac5f ends here
 
 // [[file:../remote.note::6730a02b][6730a02b]]
+use crate::worker::ComputationResult;
 use crate::Client;
 use std::path::Path;
 
-impl Client {
-    /// Request server to run `cmd` in directory `wrk_dir`.
-    pub async fn run_cmd(&self, cmd: &str, wrk_dir: &Path) -> Result<String> {
+impl Job {
+    /// Construct a job running `cmd` in directory `wrk_dir`.
+    pub(crate) fn from_cmd(cmd: &str, wrk_dir: &Path) -> Self {
+        let locality = wrk_dir.to_string_lossy();
         let wrk_dir = wrk_dir.shell_escape_lossy();
         #[rustfmt::skip]
         let script = format!("#! /usr/bin/env bash
@@ -22,12 +40,131 @@ set -x
 cd {wrk_dir}
 {cmd}
 ");
-        let job = Job::new(script);
-        let o = self.post("jobs", job).await?;
+        Job::new(script).with_locality(&locality)
+    }
+}
+
+/// The request for shutting down the scheduler
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+struct ShutdownRequest {
+    /// The time in seconds to wait for running jobs to finish
+    wait: Option<f64>,
+    /// Also shut down all registered workers
+    #[serde(default)]
+    workers: bool,
+}
+
+impl Client {
+    /// Request server to run `cmd` in directory `wrk_dir`, and wait for
+    /// its result.
+    pub async fn run_cmd(&self, cmd: &str, wrk_dir: &Path) -> Result<ComputationResult> {
+        let id = self.submit_cmd(cmd, wrk_dir).await?;
+        let o = self.wait_for_job_result(id).await?;
 
         Ok(o)
     }
 
+    /// Request server to run `cmd` in directory `wrk_dir` in background.
+    /// Return the job ID without waiting for its completion.
+    pub async fn submit_cmd(&self, cmd: &str, wrk_dir: &Path) -> Result<JobId> {
+        let job = Job::from_cmd(cmd, wrk_dir);
+        self.submit_job(job).await
+    }
+
+    /// Request server to run `job` in background. Return the job ID
+    /// without waiting for its completion.
+    pub(crate) async fn submit_job(&self, job: Job) -> Result<JobId> {
+        let o = self.post("jobs", job).await?;
+        let id = serde_json::from_str(&o).with_context(|| format!("invalid job id: {o:?}"))?;
+        info!("job submitted with id {id}");
+
+        Ok(id)
+    }
+
+    /// Request server to run array job `array` in background. Return the
+    /// array ID without waiting for its completion.
+    pub(crate) async fn submit_array(&self, array: ArrayJob) -> Result<ArrayId> {
+        let o = self.post("arrays", array).await?;
+        let id = serde_json::from_str(&o).with_context(|| format!("invalid array id: {o:?}"))?;
+        info!("array job submitted with id {id}");
+
+        Ok(id)
+    }
+
+    /// Request server to return the status of all tasks in array job `id`.
+    pub async fn array_status(&self, id: ArrayId) -> Result<ArrayStatus> {
+        let o = self.get(&format!("arrays/{id}")).await?;
+        let status = serde_json::from_str(&o).with_context(|| format!("invalid json str: {o:?}"))?;
+        Ok(status)
+    }
+
+    /// Wait for all tasks in array job `id` to finish, and return their
+    /// results in index order.
+    pub async fn wait_for_array_result(&self, id: ArrayId) -> Result<Vec<ArrayTaskResult>> {
+        let o = self.get(&format!("arrays/{id}/result")).await?;
+        let results = serde_json::from_str(&o).with_context(|| format!("invalid json str: {o:?}"))?;
+        Ok(results)
+    }
+
+    /// Request server to return the status of job `id`.
+    pub async fn job_status(&self, id: JobId) -> Result<JobStatus> {
+        let o = self.get(&format!("jobs/{id}")).await?;
+        let status = serde_json::from_str(&o).with_context(|| format!("invalid json str: {o:?}"))?;
+        Ok(status)
+    }
+
+    /// Wait for job `id` to finish, and return its result.
+    pub async fn wait_for_job_result(&self, id: JobId) -> Result<ComputationResult> {
+        let o = self.get(&format!("jobs/{id}/result")).await?;
+        ComputationResult::parse_from_json(&o)
+    }
+
+    /// Copy standard output of job `id` into `out`. If `follow` is true,
+    /// wait for the job to start, and keep copying new output until it
+    /// finished.
+    pub async fn copy_job_output(&self, id: JobId, follow: bool, mut out: impl std::io::Write) -> Result<()> {
+        let mut resp = self.get_stream(&format!("jobs/{id}/stdout?follow={follow}")).await?;
+        while let Some(chunk) = resp.chunk().await? {
+            out.write_all(&chunk)?;
+            out.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Request server to cancel job `id`.
+    pub async fn cancel_job(&self, id: JobId) -> Result<()> {
+        self.delete(&format!("jobs/{id}")).await?;
+        Ok(())
+    }
+
+    /// Request server to list jobs pending in queue in dispatching order.
+    pub async fn queued_jobs(&self) -> Result<Vec<QueuedJob>> {
+        let o = self.get("queue").await?;
+        let jobs = serde_json::from_str(&o).with_context(|| format!("invalid json str: {o:?}"))?;
+        Ok(jobs)
+    }
+
+    /// Request server to reorder pending job `id` in queue.
+    pub async fn reorder_job(&self, id: JobId, update: QueueUpdate) -> Result<()> {
+        self.put(&format!("queue/{id}"), update).await?;
+        Ok(())
+    }
+
+    /// Request server to report status of nodes and jobs.
+    pub async fn scheduler_status(&self) -> Result<SchedulerStatus> {
+        let o = self.get("status").await?;
+        let status = serde_json::from_str(&o).with_context(|| format!("invalid json str: {o:?}"))?;
+        Ok(status)
+    }
+
+    /// Request the scheduler to shut down. Running jobs will be cancelled
+    /// if they cannot finish within `wait` seconds. All registered workers
+    /// will be shut down too if `workers` is true.
+    pub async fn shutdown_scheduler(&self, wait: Option<f64>, workers: bool) -> Result<()> {
+        self.post("shutdown", ShutdownRequest { wait, workers }).await?;
+        Ok(())
+    }
+
     /// Request server to add a new node for remote computation.
     pub async fn add_node(&self, node: impl Into<Node>) -> Result<()> {
         self.post("nodes", node.into()).await?;
@@ -36,7 +173,7 @@ cd {wrk_dir}
 
     #[tokio::main()]
     /// For non-async call
-    pub(crate) async fn run_cmd_block(&self, cmd: &str, wrk_dir: &Path) -> Result<String> {
+    pub(crate) async fn run_cmd_block(&self, cmd: &str, wrk_dir: &Path) -> Result<ComputationResult> {
         let s = self.run_cmd(cmd, wrk_dir).await?;
         Ok(s)
     }
@@ -48,12 +185,15 @@ mod routes {
     use super::*;
     use crate::gchemol::Molecule;
     use crate::rest::AppError;
-    use crate::worker::ComputationResult;
+    use crate::worker::{ComputationResult, OutputQuery};
     use gosh_model::Computed;
     use dispatch::TaskClient;
 
-    use axum::extract::State;
+    use axum::extract::{self, ConnectInfo, State};
+    use axum::body::{Bytes, StreamBody};
+    use axum::response::sse::{Event as SseEvent, KeepAlive, Sse};
     use axum::Json;
+    use futures_util::{Stream, StreamExt};
 
     /// Handle request for adding a new node into `Nodes`
     #[axum::debug_handler]
@@ -69,30 +209,183 @@ mod routes {
         Ok(Json(o))
     }
 
-    /// Handle request for adding a new node into `Nodes`
+    /// Handle request for submitting a new job. Return the job ID
+    /// immediately.
     #[axum::debug_handler]
     async fn add_job(
         State(task): State<TaskClient>,
+        ConnectInfo(client): ConnectInfo<SocketAddr>,
         Json(job): Json<Job>,
+    ) -> Result<Json<JobId>, AppError> {
+        let id = task.submit_job(job, Some(client.ip()))?;
+        Ok(Json(id))
+    }
+
+    /// Handle request for submitting an array job. Return the array ID
+    /// immediately.
+    #[axum::debug_handler]
+    async fn add_array(
+        State(task): State<TaskClient>,
+        ConnectInfo(client): ConnectInfo<SocketAddr>,
+        Json(array): Json<ArrayJob>,
+    ) -> Result<Json<ArrayId>, AppError> {
+        let id = task.submit_array(array, Some(client.ip()))?;
+        Ok(Json(id))
+    }
+
+    /// Handle request for querying the status of array job `id`
+    #[axum::debug_handler]
+    async fn get_array(
+        State(task): State<TaskClient>,
+        extract::Path(id): extract::Path<ArrayId>,
+    ) -> Result<Json<ArrayStatus>, AppError> {
+        let status = task.array_status(id)?;
+        Ok(Json(status))
+    }
+
+    /// Handle request for the results of array job `id`. Wait until all
+    /// its tasks finished.
+    #[axum::debug_handler]
+    async fn get_array_result(
+        State(task): State<TaskClient>,
+        extract::Path(id): extract::Path<ArrayId>,
+    ) -> Result<Json<Vec<ArrayTaskResult>>, AppError> {
+        let results = task.wait_for_array_result(id).await?;
+        Ok(Json(results))
+    }
+
+    /// Handle request for querying the status of job `id`
+    #[axum::debug_handler]
+    async fn get_job(
+        State(task): State<TaskClient>,
+        extract::Path(id): extract::Path<JobId>,
+    ) -> Result<Json<JobStatus>, AppError> {
+        let status = task.job_status(id)?;
+        Ok(Json(status))
+    }
+
+    /// Handle request for cancelling job `id`
+    #[axum::debug_handler]
+    async fn cancel_job(State(task): State<TaskClient>, extract::Path(id): extract::Path<JobId>) -> Result<(), AppError> {
+        task.cancel_job(id).await?;
+        Ok(())
+    }
+
+    /// Handle request for listing jobs pending in queue
+    #[axum::debug_handler]
+    async fn get_queue(State(task): State<TaskClient>) -> Result<Json<Vec<QueuedJob>>, AppError> {
+        Ok(Json(task.queued_jobs()))
+    }
+
+    /// Handle request for reordering pending job `id` in queue
+    #[axum::debug_handler]
+    async fn reorder_job(
+        State(task): State<TaskClient>,
+        extract::Path(id): extract::Path<JobId>,
+        Json(update): Json<QueueUpdate>,
+    ) -> Result<(), AppError> {
+        task.reorder_job(id, &update)?;
+        Ok(())
+    }
+
+    /// Handle request for the overall status of the scheduler
+    #[axum::debug_handler]
+    async fn get_status(State(task): State<TaskClient>) -> Result<Json<SchedulerStatus>, AppError> {
+        Ok(Json(task.scheduler_status()))
+    }
+
+    /// Handle request for metrics in Prometheus text format
+    async fn get_metrics(State(task): State<TaskClient>) -> Result<crate::metrics::MetricsResponse, AppError> {
+        crate::metrics::scheduler_metrics(&task.scheduler_status())
+    }
+
+    /// Handle request for the result of job `id`. Wait until the job
+    /// finished.
+    #[axum::debug_handler]
+    async fn get_job_result(
+        State(task): State<TaskClient>,
+        extract::Path(id): extract::Path<JobId>,
     ) -> Result<Json<ComputationResult>, AppError> {
-        let r = task.run_cmd(job).await?;
-        let c = ComputationResult::parse_from_json(&r)?;
-        Ok(Json(c))
+        let r = task.wait_for_job_result(id).await?;
+        Ok(Json(r))
     }
 
-    pub(super) async fn run_restful(addr: impl Into<SocketAddr>, state: TaskClient) -> Result<()> {
-        use axum::routing::post;
+    /// Handle request for standard output of job `id`, streamed from the
+    /// node running it.
+    #[axum::debug_handler]
+    async fn get_job_stdout(
+        State(task): State<TaskClient>,
+        extract::Path(id): extract::Path<JobId>,
+        extract::Query(query): extract::Query<OutputQuery>,
+    ) -> Result<StreamBody<impl Stream<Item = reqwest::Result<Bytes>>>, AppError> {
+        let stream = task.job_output(id, query.follow).await?;
+        Ok(StreamBody::new(stream))
+    }
+
+    /// Handle request for streaming lifecycle events of jobs and nodes as
+    /// server-sent events, until the scheduler shut down.
+    #[axum::debug_handler]
+    async fn get_events(State(task): State<TaskClient>) -> Sse<impl Stream<Item = Result<SseEvent, serde_json::Error>>> {
+        let stream = task.subscribe_events().map(|event| {
+            let data = serde_json::to_value(&event)?;
+            let name = data["event"].as_str().unwrap_or("message").to_owned();
+            SseEvent::default().event(name).json_data(data)
+        });
+        Sse::new(stream).keep_alive(KeepAlive::default())
+    }
+
+    /// Handle request for shutting down the scheduler
+    #[axum::debug_handler]
+    async fn shutdown(State(task): State<TaskClient>, Json(req): Json<ShutdownRequest>) -> Result<(), AppError> {
+        task.shutdown(req.wait, req.workers).await?;
+        Ok(())
+    }
+
+    /// Serve RESTful requests on `addr` until `signal` resolved.
+    pub(super) async fn run_restful(
+        addr: impl Into<SocketAddr>,
+        state: TaskClient,
+        signal: impl std::future::Future<Output = ()>,
+    ) -> Result<()> {
+        use axum::routing::{get, post, put};
 
         let app = axum::Router::new()
             .route("/jobs", post(add_job))
             .with_state(state.clone())
+            .route("/jobs/:id", get(get_job).delete(cancel_job))
+            .with_state(state.clone())
+            .route("/jobs/:id/result", get(get_job_result))
+            .with_state(state.clone())
+            .route("/jobs/:id/stdout", get(get_job_stdout))
+            .with_state(state.clone())
+            .route("/arrays", post(add_array))
+            .with_state(state.clone())
+            .route("/arrays/:id", get(get_array))
+            .with_state(state.clone())
+            .route("/arrays/:id/result", get(get_array_result))
+            .with_state(state.clone())
+            .route("/status", get(get_status))
+            .with_state(state.clone())
+            .route("/metrics", get(get_metrics))
+            .with_state(state.clone())
+            .route("/queue", get(get_queue))
+            .with_state(state.clone())
+            .route("/queue/:id", put(reorder_job))
+            .with_state(state.clone())
             .route("/mols", post(add_mol))
             .with_state(state.clone())
             .route("/nodes", post(add_node))
+            .with_state(state.clone())
+            .route("/events", get(get_events))
+            .with_state(state.clone())
+            .route("/shutdown", post(shutdown))
             .with_state(state);
         let addr = addr.into();
 
-        let x = axum::Server::bind(&addr).serve(app.into_make_service()).await?;
+        axum::Server::bind(&addr)
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+            .with_graceful_shutdown(signal)
+            .await?;
         Ok(())
     }
 }
@@ -104,22 +397,76 @@ use gchemol::Molecule;
 /// Represent any input submited to remote node for computation.
 #[derive(Debug, Clone)]
 enum Jobx {
-    Job(Job),
+    Job(JobId, Job),
     Mol(Molecule),
 }
 
 impl Jobx {
     fn job_name(&self) -> String {
         match self {
-            Self::Job(job) => job.name(),
+            Self::Job(_, job) => job.name(),
             Self::Mol(mol) => mol.title(),
         }
     }
 
+    /// Return the resources required on each node, the constraints on
+    /// node labels, and the number of nodes required.
+    fn requirements(&self) -> (Resources, Affinity, usize) {
+        match self {
+            Self::Job(_, job) => (job.requires.clone(), job.affinity.clone(), job.nodes.max(1)),
+            Self::Mol(_) => (Resources::default(), Affinity::default(), 1),
+        }
+    }
+
+    /// Return the priority for dispatching.
+    fn priority(&self) -> i32 {
+        match self {
+            Self::Job(_, job) => job.priority,
+            Self::Mol(_) => 0,
+        }
+    }
+
+    /// Return the IDs of jobs it depends on, and whether it should run
+    /// even if some of them failed.
+    fn dependencies(&self) -> (&[JobId], bool) {
+        match self {
+            Self::Job(_, job) => (&job.depends_on, job.always_run),
+            Self::Mol(_) => (&[], false),
+        }
+    }
+
+    /// Return the key for placing the job near previous jobs.
+    fn locality(&self) -> Option<&str> {
+        match self {
+            Self::Job(_, job) => job.locality.as_deref(),
+            Self::Mol(_) => None,
+        }
+    }
+
+    /// Return the job ID assigned by the scheduler.
+    fn job_id(&self) -> Option<JobId> {
+        match self {
+            Self::Job(id, _) => Some(*id),
+            Self::Mol(_) => None,
+        }
+    }
+
+    /// Pass host names of all allocated `nodes` to the job via environment
+    /// variable `GOSH_NODES`.
+    fn with_nodes(self, nodes: &[Node]) -> Self {
+        match self {
+            Self::Job(id, job) if nodes.len() > 1 => {
+                let hosts = nodes.iter().map(|x| x.host()).join(",");
+                Self::Job(id, job.with_env("GOSH_NODES", hosts))
+            }
+            _ => self,
+        }
+    }
+
     async fn run_on(self, node: &Node) -> Result<String> {
         let client = Client::connect(node);
         match self {
-            Self::Job(job) => {
+            Self::Job(_, job) => {
                 let o = client.post("jobs", job).await?;
                 Ok(o)
             }
@@ -132,20 +479,59 @@ impl Jobx {
 }
 // 3ce50110 ends here
 
+// [[file:../remote.note::c376bde9][c376bde9]]
+/// The options for running the scheduler
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SchedulerConfig {
+    /// The policy for choosing nodes to run jobs
+    pub policy: crate::Policy,
+    /// The time in seconds a job waits for the node which ran the last
+    /// job in the same working directory, before running on other nodes.
+    /// The job never waits if zero, but still prefers that node when free.
+    pub locality_wait: f64,
+    /// The time in seconds a job can be blocked by lack of free nodes
+    /// while jobs after it go first. Then jobs after it will wait for it
+    /// to be dispatched. Unlimited if zero.
+    pub backfill_wait: f64,
+    /// The maximum number of completed results cached for reusing by
+    /// identical jobs. Caching is disabled if zero.
+    pub cache_size: usize,
+    /// The time in seconds a cached result can be reused.
+    pub cache_age: f64,
+    /// The maximum number of jobs pending in queue. New jobs will be
+    /// rejected with 503 when full. Unlimited if zero.
+    pub max_queued: usize,
+    /// The maximum number of unfinished jobs submitted from each client
+    /// host. New jobs will be rejected with 429 when exceeded. Unlimited
+    /// if zero.
+    pub max_client_jobs: usize,
+    /// The maximum number of finished jobs kept for querying their status
+    /// and results. Older ones will be reported as expired. Unlimited if
+    /// zero.
+    pub keep_finished: usize,
+    /// The time in seconds a finished job is kept. Unlimited if zero.
+    pub keep_age: f64,
+    /// Launch and retire workers on demand if set.
+    pub pool: Option<PoolConfig>,
+}
+// c376bde9 ends here
+
 // [[file:../remote.note::63fb876f][63fb876f]]
 use base::Nodes;
 use server::Server;
 
 impl Server {
-    /// Start a server as a scheduler for computational jobs.
-    pub async fn serve_as_scheduler(&self) {
+    /// Start a server as a scheduler for computational jobs, configured
+    /// with `config`.
+    pub async fn serve_as_scheduler(&self, config: SchedulerConfig) -> Result<()> {
         println!("scheduler listening on {:?}", self.address);
 
         // the server side
-        let (mut task_server, task_client) = self::dispatch::new_interactive_task();
-        let nodes: Vec<String> = vec![];
+        let nodes = Nodes::new(Vec::<Node>::new()).with_policy(config.policy);
+        let (mut task_server, task_client) = self::dispatch::new_interactive_task(&nodes, &config);
+        let pool = config.pool.map(|c| self::pool::ElasticPool::new(c, self.address)).transpose()?;
         let h1 = tokio::spawn(async move {
-            if let Err(e) = task_server.run_and_serve(Nodes::new(nodes)).await {
+            if let Err(e) = task_server.run_and_serve(nodes, pool).await {
                 error!("task server: {e:?}");
             }
         });
@@ -154,8 +540,13 @@ impl Server {
         // the client side
         let address = self.address;
         let tc = task_client.clone();
+        let stopped = tokio_util::sync::CancellationToken::new();
+        let signal = stopped.clone();
         let h2 = tokio::spawn(async move {
-            self::routes::run_restful(address, tc).await;
+            let signal = async move { signal.cancelled().await };
+            if let Err(e) = self::routes::run_restful(address, tc, signal).await {
+                error!("restful service: {e:?}");
+            }
         });
         tokio::pin!(h2);
 
@@ -163,23 +554,33 @@ impl Server {
         let h3 = crate::rest::shutdown_signal();
         tokio::pin!(h3);
 
-        loop {
-            tokio::select! {
-                _res = &mut h1 => {
-                    log_dbg!();
-                }
-                _res = &mut h2 => {
-                    log_dbg!();
-                }
-                _res = &mut h3 => {
-                    info!("User interrupted. Shutting down ...");
-                    let _ = task_client.abort().await;
-                    break;
-                }
+        let mut h1_done = false;
+        let mut h2_done = false;
+        tokio::select! {
+            _ = &mut h1 => {
+                info!("scheduler shut down as requested.");
+                h1_done = true;
+            }
+            _ = &mut h2 => {
+                error!("restful service stopped unexpectedly. Shutting down ...");
+                h2_done = true;
+            }
+            _ = &mut h3 => {
+                info!("User interrupted. Shutting down ...");
             }
         }
-        h1.abort();
-        h2.abort();
+        if !h1_done {
+            if let Err(e) = task_client.shutdown(None, false).await {
+                error!("failed to shut down task server: {e:?}");
+            }
+            let _ = h1.await;
+        }
+        // stop accepting requests after dispatching stopped
+        stopped.cancel();
+        if !h2_done {
+            let _ = h2.await;
+        }
+        Ok(())
     }
 }
 // 63fb876f ends here
@@ -0,0 +1,201 @@
+// [[file:../../remote.note::49a1804d][49a1804d]]
+#![deny(warnings)]
+
+use super::*;
+use crate::worker::ComputationResult;
+use registry::JobRegistry;
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+// 49a1804d ends here
+
+// [[file:../../remote.note::9da4f3d2][9da4f3d2]]
+/// The unique ID of an array job submitted to the scheduler
+pub type ArrayId = usize;
+
+/// The environment variable exposing the task index to the job script
+const ARRAY_INDEX_VAR: &str = "GOSH_ARRAY_INDEX";
+
+/// The tasks expanded from the template of an array job
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum ArrayTasks {
+    /// One task for each index from `start` to `end` (exclusive)
+    Range { start: usize, end: usize },
+    /// One task for each parameter set, indexed from 0. The parameters are
+    /// exposed to the job script as environment variables.
+    Params(Vec<BTreeMap<String, String>>),
+}
+
+/// The request for submitting an array job
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ArrayJob {
+    /// The template for all tasks
+    pub job: Job,
+    /// The tasks to be expanded from the template
+    pub tasks: ArrayTasks,
+}
+
+/// The status of a task in an array job
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ArrayTaskStatus {
+    /// The task index exposed as `GOSH_ARRAY_INDEX`
+    pub index: usize,
+    /// The job ID of the task
+    pub id: JobId,
+    /// The status of the job running the task. None if the job record
+    /// has expired.
+    pub status: Option<JobStatus>,
+}
+
+/// The status of an array job
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ArrayStatus {
+    /// The array ID assigned by the scheduler
+    pub id: ArrayId,
+    /// The status of all tasks in index order
+    pub tasks: Vec<ArrayTaskStatus>,
+}
+
+/// The result of a finished task in an array job
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ArrayTaskResult {
+    /// The task index exposed as `GOSH_ARRAY_INDEX`
+    pub index: usize,
+    /// The job ID of the task
+    pub id: JobId,
+    /// The computation result of the task, with error of kind `Expired`
+    /// if the result is not kept any more.
+    pub result: ComputationResult,
+}
+
+impl ArrayJob {
+    /// Expand the template into individual jobs, together with their task
+    /// indices.
+    pub(super) fn expand(&self) -> Result<Vec<(usize, Job)>> {
+        let name = self.job.name();
+        let task = |index: usize| {
+            self.job
+                .clone()
+                .with_name(&format!("{name}-{index}"))
+                .with_env(ARRAY_INDEX_VAR, index.to_string())
+        };
+        let jobs = match &self.tasks {
+            ArrayTasks::Range { start, end } => {
+                ensure!(start < end, "empty index range for array job: {start}..{end}");
+                (*start..*end).map(|i| (i, task(i))).collect()
+            }
+            ArrayTasks::Params(params) => {
+                ensure!(!params.is_empty(), "no parameter sets for array job");
+                params
+                    .iter()
+                    .enumerate()
+                    .map(|(i, p)| {
+                        let job = p.iter().fold(task(i), |job, (k, v)| job.with_env(k, v));
+                        (i, job)
+                    })
+                    .collect()
+            }
+        };
+        Ok(jobs)
+    }
+}
+
+/// Pairs of task index and job ID of all tasks in an array job
+type ArrayTaskIds = Vec<(usize, JobId)>;
+
+#[derive(Default)]
+struct Arrays {
+    next_id: ArrayId,
+    records: BTreeMap<ArrayId, ArrayTaskIds>,
+    // the number of arrays kept after last pruning
+    pruned: usize,
+}
+
+/// Book-keeping of array jobs submitted to the scheduler. An array job is
+/// kept as long as any of its tasks kept in `JobRegistry`, so that both
+/// follow the same retention limits.
+#[derive(Clone, Default)]
+pub(super) struct ArrayRegistry {
+    inner: Arc<Mutex<Arrays>>,
+}
+
+impl ArrayRegistry {
+    /// Register an array job consisting of `tasks` in pairs of task index
+    /// and job ID. Return its array ID. Array jobs with all tasks expired
+    /// from `jobs` will be forgotten.
+    pub fn register(&self, tasks: ArrayTaskIds, jobs: &JobRegistry) -> ArrayId {
+        let mut arrays = self.inner.lock().unwrap();
+        // prune only when the number of arrays doubled, for not scanning
+        // all tasks on each registration
+        if arrays.records.len() > 2 * arrays.pruned {
+            arrays.records.retain(|_, tasks| tasks.iter().any(|(_, id)| jobs.contains(*id)));
+            arrays.pruned = arrays.records.len();
+        }
+        let id = arrays.next_id;
+        arrays.next_id += 1;
+        arrays.records.insert(id, tasks);
+        id
+    }
+
+    /// Return all tasks in array job `id`.
+    pub fn tasks(&self, id: ArrayId) -> Result<ArrayTaskIds> {
+        let arrays = self.inner.lock().unwrap();
+        match arrays.records.get(&id) {
+            Some(tasks) => Ok(tasks.clone()),
+            None if id < arrays.next_id => bail!("array job {id} expired: all its tasks expired"),
+            None => bail!("no array job with id {id}"),
+        }
+    }
+}
+// 9da4f3d2 ends here
+
+// [[file:../../remote.note::aab7382d][aab7382d]]
+#[test]
+fn test_array_job() -> Result<()> {
+    let array = ArrayJob {
+        job: Job::new("test").with_name("sweep"),
+        tasks: ArrayTasks::Range { start: 2, end: 5 },
+    };
+    let jobs = array.expand()?;
+    assert_eq!(jobs.len(), 3);
+    assert_eq!(jobs[0].0, 2);
+    assert_eq!(jobs[0].1.name(), "sweep-2");
+    assert_eq!(jobs[2].1.env[ARRAY_INDEX_VAR], "4");
+
+    let params = vec![
+        [("T".to_owned(), "300".to_owned())].into(),
+        [("T".to_owned(), "500".to_owned())].into(),
+    ];
+    let array = ArrayJob {
+        job: Job::new("test"),
+        tasks: ArrayTasks::Params(params),
+    };
+    let jobs = array.expand()?;
+    assert_eq!(jobs[1].1.env[ARRAY_INDEX_VAR], "1");
+    assert_eq!(jobs[1].1.env["T"], "500");
+
+    let array = ArrayJob {
+        job: Job::new("test"),
+        tasks: ArrayTasks::Range { start: 1, end: 1 },
+    };
+    assert!(array.expand().is_err());
+
+    let jobs = JobRegistry::default().with_retention(1, 0.0);
+    let registry = ArrayRegistry::default();
+    let job0 = jobs.register("job0".into());
+    let id = registry.register(vec![(0, job0)], &jobs);
+    assert_eq!(registry.tasks(id)?, [(0, job0)]);
+    assert!(registry.tasks(id + 1).is_err());
+
+    // forgotten once all tasks expired
+    jobs.cancel_queued(job0, "test")?;
+    let job1 = jobs.register("job1".into());
+    jobs.cancel_queued(job1, "test")?;
+    assert!(!jobs.contains(job0));
+    let id1 = registry.register(vec![(0, job1)], &jobs);
+    let err = registry.tasks(id).unwrap_err();
+    assert!(err.to_string().contains("expired"), "{err}");
+    assert!(registry.tasks(id1).is_ok());
+    Ok(())
+}
+// aab7382d ends here
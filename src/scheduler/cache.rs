@@ -0,0 +1,147 @@
+// [[file:../../remote.note::4e8c6773][4e8c6773]]
+#![deny(warnings)]
+
+use super::*;
+use crate::worker::ComputationResult;
+
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+// 4e8c6773 ends here
+
+// [[file:../../remote.note::641df713][641df713]]
+/// The cache of completed results, keyed by the hash of job script,
+/// environment variables and input file contents
+#[derive(Clone, Default)]
+pub(super) struct ResultCache {
+    inner: Arc<Mutex<HashMap<String, (ComputationResult, Instant)>>>,
+    // the maximum number of results kept. Caching is disabled if zero.
+    capacity: usize,
+    // results older than this will be dropped
+    max_age: Duration,
+}
+
+/// Hash the script, environment variables and input files of `job`.
+fn hash_job(job: &Job) -> Result<String> {
+    let mut hasher = Sha256::new();
+    hasher.update(serde_json::to_string(&(&job.script, &job.env, &job.inputs))?);
+    for path in &job.inputs {
+        let mut f = std::fs::File::open(path).with_context(|| format!("failed to open input file {path:?}"))?;
+        std::io::copy(&mut f, &mut hasher)?;
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+impl ResultCache {
+    /// Construct a cache keeping at most `capacity` results, each for at
+    /// most `max_age` seconds.
+    pub fn new(capacity: usize, max_age: f64) -> Self {
+        Self {
+            capacity,
+            max_age: Duration::from_secs_f64(max_age),
+            ..Default::default()
+        }
+    }
+
+    /// Return the cache key for `job`, or None if its result should not
+    /// be cached. Jobs with dependencies are never cached, as their input
+    /// files may not be ready at submission.
+    pub async fn key(&self, job: &Job) -> Option<String> {
+        if self.capacity == 0 || job.no_cache || !job.depends_on.is_empty() {
+            return None;
+        }
+        let job = job.clone();
+        match tokio::task::spawn_blocking(move || hash_job(&job)).await {
+            Ok(Ok(key)) => Some(key),
+            Ok(Err(err)) => {
+                warn!("result will not be cached: {err:?}");
+                None
+            }
+            Err(err) => {
+                error!("hashing job panicked: {err:?}");
+                None
+            }
+        }
+    }
+
+    /// Return the result cached under `key`, if not expired.
+    pub fn get(&self, key: &str) -> Option<ComputationResult> {
+        let mut entries = self.inner.lock().unwrap();
+        let (result, stored) = entries.get(key)?;
+        if stored.elapsed() > self.max_age {
+            entries.remove(key);
+            return None;
+        }
+        Some(result.clone())
+    }
+
+    /// Cache `result` under `key` if the job completed successfully.
+    /// Expired results will be dropped, and the oldest ones too if the
+    /// cache is full.
+    pub fn put(&self, key: String, result: &ComputationResult) {
+        if self.capacity == 0 || result.error.is_some() {
+            return;
+        }
+        let mut entries = self.inner.lock().unwrap();
+        entries.retain(|_, (_, stored)| stored.elapsed() <= self.max_age);
+        while entries.len() >= self.capacity {
+            let oldest = entries.iter().min_by_key(|(_, (_, stored))| *stored).map(|(k, _)| k.clone());
+            if let Some(k) = oldest {
+                entries.remove(&k);
+            }
+        }
+        entries.insert(key, (result.clone(), Instant::now()));
+    }
+}
+// 641df713 ends here
+
+// [[file:../../remote.note::f6d42bdb][f6d42bdb]]
+#[tokio::test]
+async fn test_result_cache() -> Result<()> {
+    use crate::worker::ErrorKind;
+
+    let dir = tempfile::tempdir()?;
+    let input = dir.path().join("POSCAR");
+    std::fs::write(&input, "H 0 0 0")?;
+
+    let cache = ResultCache::new(2, 60.0);
+    let job = Job::new("cat POSCAR").with_inputs(vec![input.clone()]);
+    let key = cache.key(&job).await.unwrap();
+    // job names make no difference
+    assert_eq!(cache.key(&job.clone().with_name("job1")).await, Some(key.clone()));
+    assert!(cache.key(&job.clone().with_no_cache(true)).await.is_none());
+    // different input file contents
+    std::fs::write(&input, "He 0 0 0")?;
+    let key1 = cache.key(&job).await.unwrap();
+    assert_ne!(key, key1);
+    // missing input file
+    let missing = job.clone().with_inputs(vec![dir.path().join("WAVECAR")]);
+    assert!(cache.key(&missing).await.is_none());
+
+    // only completed results are cached
+    assert!(cache.get(&key).is_none());
+    cache.put(key.clone(), &ComputationResult::error(ErrorKind::Failed, "err"));
+    assert!(cache.get(&key).is_none());
+    let done = ComputationResult { stdout: "ok".into(), ..Default::default() };
+    cache.put(key.clone(), &done);
+    assert_eq!(cache.get(&key), Some(done.clone()));
+
+    // the oldest dropped when full
+    cache.put(key1.clone(), &done);
+    cache.put("other".into(), &done);
+    assert!(cache.get(&key).is_none());
+    assert!(cache.get(&key1).is_some());
+
+    // expired
+    let cache = ResultCache::new(2, 0.0);
+    cache.put(key.clone(), &done);
+    std::thread::sleep(Duration::from_millis(1));
+    assert!(cache.get(&key).is_none());
+
+    // disabled
+    let cache = ResultCache::default();
+    assert!(cache.key(&job).await.is_none());
+    Ok(())
+}
+// f6d42bdb ends here
@@ -4,7 +4,15 @@
 use super::*;
 use crate::task::Task;
 
+use array::{ArrayId, ArrayJob, ArrayRegistry};
+use cache::ResultCache;
+use events::{Event, EventBus};
+use limits::{JobLimits, JobPermit};
+use pool::ElasticPool;
 use base::{Node, Nodes};
+use queue::{JobQueue, QueueUpdate, QueuedJob, SHUTDOWN_REASON};
+use registry::{JobId, JobRegistry, JobStatus};
+use tokio_util::sync::CancellationToken;
 // ae9e9435 ends here
 
 // [[file:../../remote.note::55bd52fb][55bd52fb]]
@@ -14,7 +22,10 @@ use crate::task::{TaskReceiver, TaskSender};
 #[derive(Debug, Clone)]
 enum Control {
     AddNode(Node),
-    Abort,
+    /// Shut down the scheduler, waiting for running jobs to finish within
+    /// the deadline in seconds if any. All registered workers will also
+    /// be shut down if `workers` is true.
+    Shutdown { wait: Option<f64>, workers: bool },
 }
 
 // struct Jobx {}
@@ -33,24 +44,249 @@ pub(super) struct TaskClient {
     tx_ctl: TxControl,
     // for interaction with child process on server side
     tx_int: TxInteraction,
+    // for book-keeping of submitted jobs
+    jobs: JobRegistry,
+    // for pending jobs waiting for dispatching
+    queue: JobQueue,
+    // for book-keeping of submitted array jobs
+    arrays: ArrayRegistry,
+    // for reusing results of identical jobs
+    cache: ResultCache,
+    // for rejecting jobs over the limits
+    limits: JobLimits,
+    // for inspecting usage of remote nodes
+    nodes: Nodes,
+    // the time when the scheduler started
+    started: std::time::Instant,
+    // cancelled when the scheduler is shutting down
+    shutting_down: CancellationToken,
+}
+
+/// Request the node running job described in `status` to cancel it.
+async fn cancel_running_job(status: &JobStatus) -> Result<()> {
+    let id = status.id;
+    let node = status.node.as_ref().ok_or(format_err!("no node for running job {id}"))?;
+    let token = status.token.as_ref().ok_or(format_err!("no token for running job {id}"))?;
+    info!("request node {node} to cancel running job {id}");
+    Client::connect(node).delete(&format!("jobs/{token}")).await?;
+    Ok(())
 }
 
 mod client {
     use super::*;
+    use crate::worker::{ComputationResult, ErrorKind};
     use gosh_model::Computed;
+    use array::{ArrayStatus, ArrayTaskResult, ArrayTaskStatus};
+    use axum::body::Bytes;
+    use futures_util::stream::{self, BoxStream, StreamExt};
+    use registry::JobState;
+    use status::SchedulerStatus;
+    use std::net::IpAddr;
 
     impl TaskClient {
-        /// Send `job` to target and wait for output. Return the
-        /// computed result.
-        pub async fn run_cmd(&self, job: Job) -> Result<String> {
-            let out = self.tx_int.send(Jobx::Job(job)).await?;
-            Ok(out)
+        /// Check if `job` can be accepted.
+        fn check_job(&self, job: &Job) -> Result<()> {
+            ensure!(!self.shutting_down.is_cancelled(), "job rejected: {SHUTDOWN_REASON}");
+            // or the job would be held in queue forever
+            let n = job.nodes.max(1);
+            ensure!(
+                self.nodes.could_fit(&job.requires, &job.affinity, n),
+                "job {} rejected: no registered node can fit it ({n} nodes each with {:?}, {:?})",
+                job.name(),
+                job.requires,
+                job.affinity,
+            );
+            // a job can only depend on jobs submitted before, so there will
+            // be no cycle in dependencies
+            for dep in &job.depends_on {
+                self.jobs.status(*dep).with_context(|| format!("invalid dependency for job {}", job.name()))?;
+            }
+            Ok(())
+        }
+
+        /// Submit `job` from `client` for computation in background.
+        /// Return the job ID immediately without waiting for its
+        /// completion. The job will be rejected if over the limits.
+        pub fn submit_job(&self, job: Job, client: Option<IpAddr>) -> Result<JobId> {
+            self.check_job(&job)?;
+            let mut permits = self.limits.admit(client, 1)?;
+            Ok(self.spawn_job(job, permits.remove(0)))
+        }
+
+        /// Run `job` admitted with `permit` in background. Return the job
+        /// ID.
+        fn spawn_job(&self, job: Job, permit: JobPermit) -> JobId {
+            let id = self.jobs.register(job.name());
+            self.jobs.hold_dependencies(id, &job.depends_on);
+            let task = self.clone();
+            tokio::spawn(async move {
+                let mut permit = permit;
+                let key = task.cache.key(&job).await;
+                if let Some(result) = key.as_ref().and_then(|k| task.cache.get(k)) {
+                    info!("job {id} completed with cached result");
+                    crate::metrics::scheduler().cache_hit();
+                    task.jobs.set_finished(id, result);
+                    return;
+                }
+                // the job may be cancelled while hashing its input files
+                if task.jobs.status(id).map_or(true, |s| s.state.is_finished()) {
+                    return;
+                }
+                let sent = task.tx_int.send(Jobx::Job(id, job));
+                tokio::pin!(sent);
+                let out = tokio::select! {
+                    out = &mut sent => out,
+                    _ = task.jobs.wait_for_start(id) => {
+                        permit.dispatched();
+                        sent.await
+                    }
+                };
+                let result = match out {
+                    Ok(out) => match ComputationResult::parse_from_json(&out) {
+                        Ok(r) => r,
+                        Err(_) => ComputationResult::error(ErrorKind::Transport, out),
+                    },
+                    Err(err) => ComputationResult::error(ErrorKind::Transport, format!("{err:#}")),
+                };
+                if let Some(key) = key {
+                    task.cache.put(key, &result);
+                }
+                task.jobs.set_finished(id, result);
+            });
+            id
+        }
+
+        /// Submit all tasks of `array` from `client` for computation in
+        /// background. Return the array ID immediately. All tasks will be
+        /// rejected if over the limits.
+        pub fn submit_array(&self, array: ArrayJob, client: Option<IpAddr>) -> Result<ArrayId> {
+            self.check_job(&array.job)?;
+            let jobs = array.expand()?;
+            let n = jobs.len();
+            let permits = self.limits.admit(client, n)?;
+            let tasks = jobs
+                .into_iter()
+                .zip(permits)
+                .map(|((index, job), permit)| (index, self.spawn_job(job, permit)))
+                .collect();
+            let id = self.arrays.register(tasks, &self.jobs);
+            info!("array job {id} submitted with {n} tasks");
+            Ok(id)
+        }
+
+        /// Return the status of all tasks in array job `id`.
+        pub fn array_status(&self, id: ArrayId) -> Result<ArrayStatus> {
+            let tasks = self
+                .arrays
+                .tasks(id)?
+                .into_iter()
+                .map(|(index, job_id)| ArrayTaskStatus {
+                    index,
+                    id: job_id,
+                    status: self.jobs.status(job_id).ok(),
+                })
+                .collect();
+            Ok(ArrayStatus { id, tasks })
+        }
+
+        /// Wait for all tasks in array job `id` to finish, and return their
+        /// results in index order.
+        pub async fn wait_for_array_result(&self, id: ArrayId) -> Result<Vec<ArrayTaskResult>> {
+            let mut results = vec![];
+            for (index, job_id) in self.arrays.tasks(id)? {
+                // tasks finished long ago may have expired
+                let result = match self.jobs.wait_for_result(job_id).await {
+                    Ok(result) => result,
+                    Err(err) => ComputationResult::error(ErrorKind::Expired, format!("{err:#}")),
+                };
+                results.push(ArrayTaskResult { index, id: job_id, result });
+            }
+            Ok(results)
+        }
+
+        /// Return the status of job `id`.
+        pub fn job_status(&self, id: JobId) -> Result<JobStatus> {
+            self.jobs.status(id)
+        }
+
+        /// Wait for job `id` to finish, and return its result.
+        pub async fn wait_for_job_result(&self, id: JobId) -> Result<ComputationResult> {
+            self.jobs.wait_for_result(id).await
+        }
+
+        /// Stream standard output of job `id` from the node running it, or
+        /// from its result if finished already. If `follow` is true, wait
+        /// for a queued job to start, and keep streaming until it finished.
+        pub async fn job_output(&self, id: JobId, follow: bool) -> Result<BoxStream<'static, reqwest::Result<Bytes>>> {
+            let status = if follow {
+                self.jobs.wait_for_start(id).await?
+            } else {
+                self.jobs.status(id)?
+            };
+            if status.state == JobState::Running {
+                let node = status.node.as_ref().ok_or(format_err!("no node for running job {id}"))?;
+                let token = status.token.as_ref().ok_or(format_err!("no token for running job {id}"))?;
+                let end_point = format!("jobs/{token}/stdout?follow={follow}");
+                match Client::connect(node).get_stream(&end_point).await {
+                    Ok(resp) => {
+                        let stream = stream::unfold(resp, |mut resp| async move {
+                            resp.chunk().await.transpose().map(|chunk| (chunk, resp))
+                        });
+                        return Ok(stream.boxed());
+                    }
+                    // the job may have finished in the meantime
+                    Err(err) if !self.jobs.status(id)?.state.is_finished() => return Err(err),
+                    Err(_) => {}
+                }
+            }
+            ensure!(self.jobs.status(id)?.state.is_finished(), "job {id} not started yet");
+            let stdout = self.jobs.wait_for_result(id).await?.stdout;
+            Ok(stream::once(async move { Ok(stdout.into()) }).boxed())
+        }
+
+        /// Cancel job `id`. A queued job will be removed from queue, and a
+        /// running job will be terminated on the remote node.
+        pub async fn cancel_job(&self, id: JobId) -> Result<()> {
+            let status = self.jobs.cancel_queued(id, "cancelled by client")?;
+            match status.state {
+                JobState::Cancelled => {
+                    self.queue.remove(id);
+                    info!("job {id} cancelled");
+                }
+                JobState::Running => cancel_running_job(&status).await?,
+                state => bail!("job {id} cannot be cancelled in state {state:?}"),
+            }
+            Ok(())
+        }
+
+        /// Return all jobs pending in queue in dispatching order.
+        pub fn queued_jobs(&self) -> Vec<QueuedJob> {
+            self.queue.list()
+        }
+
+        /// Reorder pending job `id` in queue.
+        pub fn reorder_job(&self, id: JobId, update: &QueueUpdate) -> Result<()> {
+            self.queue.reorder(id, update)
+        }
+
+        /// Subscribe to lifecycle events of jobs and nodes.
+        pub fn subscribe_events(&self) -> impl futures_util::Stream<Item = Event> {
+            self.jobs.events().subscribe()
+        }
+
+        /// Return the overall status of the scheduler.
+        pub fn scheduler_status(&self) -> SchedulerStatus {
+            let uptime = self.started.elapsed().as_secs_f64();
+            let queued = self.queue.list().len();
+            SchedulerStatus::summarize(uptime, queued, self.nodes.usage(), self.jobs.all())
         }
 
         /// Request to compute molecule
         pub async fn compute_molecule(&self, mol: Molecule) -> Result<Computed> {
             // FIXME: refactor required
             info!("Request server to compute molecule {}", mol.title());
+            ensure!(!self.shutting_down.is_cancelled(), "molecule rejected: {SHUTDOWN_REASON}");
+            self.limits.check_queue(self.queue.len(), 1)?;
             let out = self.tx_int.send(Jobx::Mol(mol)).await?;
             let computed = serde_json::from_str(&out).with_context(|| format!("invalid json str: {out:?}"))?;
             Ok(computed)
@@ -63,12 +299,19 @@ mod client {
             Ok(())
         }
 
-        /// Notify main thread to exit
-        pub async fn abort(&self) -> Result<()> {
-            debug!("send abort ctrl msg");
-            // FIXME: there is a blocking issue if nodes is empty
-            self.tx_ctl.send(Control::AddNode("localhost:3030".into())).await?;
-            self.tx_ctl.send(Control::Abort).await?;
+        /// Shut down the scheduler. New jobs will be rejected, and pending
+        /// jobs in queue will be cancelled. Running jobs will be cancelled
+        /// on remote nodes if they cannot finish within `wait` seconds.
+        /// Registered workers will be shut down too if `workers` is true.
+        /// Return when dispatching stopped.
+        pub async fn shutdown(&self, wait: Option<f64>, workers: bool) -> Result<()> {
+            if self.shutting_down.is_cancelled() {
+                info!("scheduler is already shutting down");
+                return Ok(());
+            }
+            debug!("send shutdown ctrl msg");
+            self.shutting_down.cancel();
+            self.tx_ctl.send(Control::Shutdown { wait, workers }).await?;
             Ok(())
         }
     }
@@ -81,92 +324,363 @@ pub(super) struct TaskServer {
     rx_int: Option<RxInteraction>,
     // for controlling child process
     rx_ctl: Option<RxControl>,
+    // for book-keeping of submitted jobs
+    jobs: JobRegistry,
+    // for pending jobs waiting for dispatching
+    queue: JobQueue,
 }
 
 mod server {
     use super::*;
     use crate::task::RemoteIO;
+    use crate::worker::ComputationResult;
+    use registry::JobState;
+    use tokio::task::JoinSet;
+
+    /// The time interval in seconds between health checks of remote nodes
+    const HEALTH_CHECK_INTERVAL: f64 = 10.0;
+    /// The time in seconds to wait for response of health check
+    const HEALTH_CHECK_TIMEOUT: f64 = 5.0;
+    /// The time in seconds to wait for cancelled jobs to be reported back
+    /// from remote nodes on shutdown
+    const CANCEL_TIMEOUT: f64 = 10.0;
+    /// The time in seconds to wait for workers to confirm shutdown
+    const WORKER_SHUTDOWN_TIMEOUT: f64 = 10.0;
+
+    /// Put `job` back to queue as its node is down.
+    fn requeue_job(job: RemoteIO<Jobx, String>, registry: &JobRegistry, queue: &JobQueue) -> Result<()> {
+        let RemoteIO(jobx, tx_resp) = job;
+        if let Jobx::Job(id, _) = &jobx {
+            if !registry.set_queued(*id) {
+                info!("job {id} is not running any more, skipped requeue.");
+                tx_resp.send(String::new()).ok();
+                return Ok(());
+            }
+        }
+        info!("requeue job {}", jobx.job_name());
+        crate::metrics::scheduler().job_requeued();
+        queue.push(RemoteIO(jobx, tx_resp));
+        Ok(())
+    }
+
+    /// Put `job` failed on `node` back to queue after a backoff delay if
+    /// its retry policy allows. Otherwise the job will be returned back.
+    fn retry_job(
+        job: RemoteIO<Jobx, String>,
+        node: &Node,
+        registry: &JobRegistry,
+        queue: &JobQueue,
+    ) -> Option<RemoteIO<Jobx, String>> {
+        let RemoteIO(Jobx::Job(id, j), tx_resp) = job else {
+            return Some(job);
+        };
+        let failures = registry.add_failed_node(id, node);
+        let Some(delay) = j.retry.delay(failures) else {
+            return Some(RemoteIO(Jobx::Job(id, j), tx_resp));
+        };
+        if !registry.set_queued(id) {
+            info!("job {id} is not running any more, skipped retry.");
+            tx_resp.send(String::new()).ok();
+            return None;
+        }
+        info!("job {id} failed {failures} times, retry in {delay} seconds");
+        crate::metrics::scheduler().job_retried();
+        let queue = queue.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_secs_f64(delay)).await;
+            queue.push(RemoteIO(Jobx::Job(id, j), tx_resp));
+        });
+        None
+    }
 
-    type RxJobs = spmc::Receiver<RemoteIO<Jobx, String>>;
+    /// Return true if `out` from remote node indicates the job exited
+    /// unsuccessfully, and it should be retried according to its policy.
+    fn should_retry_on_failure(job: &Jobx, out: &str) -> bool {
+        match job {
+            Jobx::Job(_, j) if j.retry.on_failure => {
+                ComputationResult::parse_from_json(out).is_ok_and(|r| r.state() == JobState::Failed)
+            }
+            _ => false,
+        }
+    }
 
-    /// compute job from `jobs` using `node`
-    async fn handle_client_interaction(jobs: RxJobs, node: &Node) -> Result<()> {
-        let RemoteIO(job, tx_resp) = jobs.recv()?;
+    /// Request `node` to cancel jobs left there when they were requeued
+    /// as the node was found down, so that no job runs twice. Return false
+    /// if the node is unreachable, and the requests will be sent again on
+    /// next call.
+    async fn cancel_stale_jobs(nodes: &Nodes, node: &Node) -> bool {
+        let timeout = std::time::Duration::from_secs_f64(HEALTH_CHECK_TIMEOUT);
+        let mut cancelled = true;
+        for token in nodes.take_stale_jobs(node) {
+            let end_point = format!("jobs/{token}");
+            match tokio::time::timeout(timeout, Client::connect(node).delete(&end_point)).await {
+                Ok(Ok(_)) => info!("stale job {token:?} cancelled on node {node}"),
+                // the node answered, but refused to cancel it
+                Ok(Err(err)) if !err.is::<reqwest::Error>() => warn!("failed to cancel stale job {token:?} on node {node}: {err}"),
+                _ => {
+                    warn!("failed to cancel stale job {token:?} on unreachable node {node}, retry later.");
+                    nodes.add_stale_job(node, &token);
+                    cancelled = false;
+                }
+            }
+        }
+        cancelled
+    }
+
+    /// Put `job` interrupted as `node` is down back to queue. The job
+    /// left on the node will be cancelled before requeued, or once the
+    /// node is reachable again.
+    async fn requeue_from_node(
+        job: RemoteIO<Jobx, String>,
+        node: &Node,
+        nodes: &Nodes,
+        registry: &JobRegistry,
+        queue: &JobQueue,
+    ) -> Result<()> {
+        if let Jobx::Job(_, j) = &job.0 {
+            nodes.add_stale_job(node, &j.token());
+            cancel_stale_jobs(nodes, node).await;
+        }
+        requeue_job(job, registry, queue)
+    }
+
+    /// compute `job` using `node`
+    async fn handle_client_interaction(
+        job: RemoteIO<Jobx, String>,
+        node: &Node,
+        nodes: &Nodes,
+        registry: &JobRegistry,
+        queue: &JobQueue,
+    ) -> Result<()> {
+        let RemoteIO(job, tx_resp) = job;
         let name = job.job_name();
+        let job = match job {
+            Jobx::Job(id, j) => {
+                // job names may be duplicated, so each run is identified by
+                // a unique token on the node
+                let token = format!("{id}-{}", crate::base::random_name());
+                if !registry.set_running(id, node, &token) {
+                    info!("job {id} is not in queue any more, skipped.");
+                    tx_resp.send(String::new()).ok();
+                    return Ok(());
+                }
+                Jobx::Job(id, j.with_token(token))
+            }
+            job => job,
+        };
 
         info!("Request remote node {node:?} to compute job {name} ...");
-        // FIXME: potentially deadlock
-        // if computation failed, we should tell the client to exit
-        match job.run_on(node).await {
-            Ok(out) => {
+        // requeue the job if the node is found down during computation
+        let node_down = nodes.down_token(node);
+        let result = tokio::select! {
+            r = job.clone().run_on(node) => Some(r),
+            _ = node_down.cancelled() => None,
+        };
+        match result {
+            Some(Ok(out)) if should_retry_on_failure(&job, &out) => {
+                warn!("Jobx {name:?} failed on node {node}");
+                if let Some(RemoteIO(_, tx_resp)) = retry_job(RemoteIO(job, tx_resp), node, registry, queue) {
+                    tx_resp.send(out).ok();
+                }
+            }
+            Some(Ok(out)) => {
                 info!("Jobx {name} completed, sending stdout to the client ...");
-                if let Err(_) = tx_resp.send(out) {
+                if tx_resp.send(out).is_err() {
                     error!("the client has been dropped");
                 }
             }
-            Err(err) => {
+            Some(Err(err)) if !Client::connect(node).is_alive(HEALTH_CHECK_TIMEOUT).await => {
+                warn!("Jobx {name:?} failed as node {node} is unreachable: {err:?}");
+                mark_down(nodes, node, registry.events());
+                requeue_from_node(RemoteIO(job, tx_resp), node, nodes, registry, queue).await?;
+            }
+            Some(Err(err)) => {
                 let msg = format!("Jobx {name:?} failed with error: {err:?}");
-                tx_resp.send(msg).ok();
+                warn!("{msg}");
+                if let Some(RemoteIO(_, tx_resp)) = retry_job(RemoteIO(job, tx_resp), node, registry, queue) {
+                    tx_resp.send(msg).ok();
+                }
+            }
+            None => {
+                warn!("Jobx {name:?} interrupted as node {node} is down");
+                requeue_from_node(RemoteIO(job, tx_resp), node, nodes, registry, queue).await?;
             }
         }
 
         Ok(())
     }
 
-    /// ask a node from `nodes` to compute one job from `jobs`
-    async fn borrow_node_and_compute(nodes: Nodes, jobs: RxJobs) {
-        match nodes.borrow_node() {
-            Ok(node) => {
-                if let Err(err) = handle_client_interaction(jobs, &node).await {
-                    error!("found error when running job: {err:?}");
+    /// compute `job` on `borrowed` nodes from `nodes`, and return them back
+    /// when done
+    async fn compute_on_nodes(
+        job: RemoteIO<Jobx, String>,
+        borrowed: Vec<Node>,
+        nodes: Nodes,
+        queue: JobQueue,
+        registry: JobRegistry,
+    ) {
+        let RemoteIO(job, tx_resp) = job;
+        let (requires, ..) = job.requirements();
+        // the job is started on the first node
+        let node = &borrowed[0];
+        let job = RemoteIO(job.with_nodes(&borrowed), tx_resp);
+        if let Err(err) = handle_client_interaction(job, node, &nodes, &registry, &queue).await {
+            error!("found error when running job: {err:?}");
+        }
+        // return nodes back when job done
+        if let Err(err) = nodes.return_nodes(&borrowed, &requires) {
+            error!("found error when return nodes: {err:?}");
+        }
+    }
+
+    /// Mark `node` as down, and announce it on `events` if it was up.
+    fn mark_down(nodes: &Nodes, node: &Node, events: &EventBus) {
+        if nodes.mark_down(node) {
+            events.send(Event::NodeDown { node: node.name().into() });
+        }
+    }
+
+    /// Check health of `node`. An unreachable node will be marked down,
+    /// and brought up again once reachable and jobs left there cancelled.
+    /// Changes are announced on `events`.
+    async fn check_node(nodes: &Nodes, node: &Node, events: &EventBus) {
+        if !Client::connect(node).is_alive(HEALTH_CHECK_TIMEOUT).await {
+            mark_down(nodes, node, events);
+            return;
+        }
+        if !cancel_stale_jobs(nodes, node).await {
+            return;
+        }
+        match nodes.mark_up(node) {
+            Ok(true) => events.send(Event::NodeUp { node: node.name().into() }),
+            Ok(false) => {}
+            Err(err) => error!("found error when bring node {node} up: {err:?}"),
+        }
+    }
+
+    /// Check health of all registered nodes at the same time, so that
+    /// unreachable nodes will not delay checking others.
+    pub(super) async fn check_nodes(nodes: &Nodes, events: &EventBus) {
+        let registered = nodes.registered();
+        let checks = registered.iter().map(|node| check_node(nodes, node, events));
+        futures_util::future::join_all(checks).await;
+    }
+
+    /// Check health of all registered nodes periodically.
+    async fn watch_nodes(nodes: Nodes, events: EventBus) {
+        let interval = std::time::Duration::from_secs_f64(HEALTH_CHECK_INTERVAL);
+        loop {
+            tokio::time::sleep(interval).await;
+            check_nodes(&nodes, &events).await;
+        }
+    }
+
+    /// Wait until all tasks in `running` finished.
+    async fn join_all(running: &mut JoinSet<()>) {
+        while running.join_next().await.is_some() {}
+    }
+
+    /// Request all registered `nodes` to shut down, and wait for their
+    /// confirmations.
+    async fn shutdown_workers(nodes: &Nodes) {
+        let mut requests = JoinSet::new();
+        for node in nodes.registered() {
+            requests.spawn(async move {
+                let ret = Client::connect(&node).shutdown_worker(WORKER_SHUTDOWN_TIMEOUT).await;
+                (node, ret)
+            });
+        }
+        while let Some(Ok((node, ret))) = requests.join_next().await {
+            match ret {
+                Ok(_) => info!("worker on node {node} shut down"),
+                Err(err) => warn!("failed to shut down worker on node {node}: {err:?}"),
+            }
+        }
+    }
+
+    impl TaskServer {
+        /// Shut down dispatching: cancel all pending jobs, and wait for
+        /// jobs `running` on remote nodes to finish within `wait` seconds.
+        /// Jobs still running after that will be cancelled on remote nodes.
+        async fn shutdown(&self, mut running: JoinSet<()>, wait: Option<f64>) {
+            use std::time::Duration;
+            use tokio::time::timeout;
+
+            info!("scheduler shutting down: {} jobs in queue cancelled", self.queue.list().len());
+            self.queue.close(&self.jobs);
+            if let Some(wait) = wait {
+                info!("wait {wait} seconds for running jobs to finish ...");
+                if timeout(Duration::from_secs_f64(wait), join_all(&mut running)).await.is_ok() {
+                    return;
                 }
-                // return node back when job done
-                if let Err(err) = nodes.return_node(node) {
-                    error!("found error when return node: {err:?}");
+            }
+            let jobs = self.jobs.all().into_iter().filter(|x| x.state == JobState::Running);
+            for status in jobs {
+                if let Err(err) = cancel_running_job(&status).await {
+                    warn!("failed to cancel job {}: {err:?}", status.id);
                 }
             }
-            Err(err) => {
-                error!("found error when borrowing node: {err:?}");
+            if timeout(Duration::from_secs_f64(CANCEL_TIMEOUT), join_all(&mut running)).await.is_err() {
+                warn!("some running jobs not responding, aborted.");
             }
+            running.shutdown().await;
         }
-    }
 
-    impl TaskServer {
-        /// Run child process in new session, and serve requests for interactions.
-        pub async fn run_and_serve(&mut self, nodes: Nodes) -> Result<()> {
+        /// Run child process in new session, and serve requests for
+        /// interactions. Workers will be launched and retired on demand
+        /// if `pool` is given.
+        pub async fn run_and_serve(&mut self, nodes: Nodes, pool: Option<ElasticPool>) -> Result<()> {
             let mut rx_int = self.rx_int.take().context("no rx_int")?;
             let mut rx_ctl = self.rx_ctl.take().context("no rx_ctl")?;
 
-            let (mut tx_jobs, rx_jobs) = spmc::channel();
-            for i in 0.. {
-                // make sure run in parallel
-                let mut join_handler = {
-                    let jobs = rx_jobs.clone();
-                    let nodes = nodes.clone();
-                    tokio::spawn(async move {
-                        let n = nodes.len();
-                        info!("task {i}: wait for remote node to compute incoming job");
-                        info!("task {i}: we have {n} nodes available for computations");
-                        borrow_node_and_compute(nodes, jobs).await;
-                    })
-                };
-                // handle logic in main thread
+            let health_check = tokio::spawn(watch_nodes(nodes.clone(), self.jobs.events().clone()));
+            let pool_stopped = CancellationToken::new();
+            let registry = self.jobs.clone();
+            let pool = pool.map(|p| tokio::spawn(p.run(nodes.clone(), self.queue.clone(), registry, pool_stopped.clone())));
+            // one task for each job running on remote nodes
+            let mut running = JoinSet::new();
+            loop {
                 tokio::select! {
-                    Ok(_) = &mut join_handler => {
-                        log_dbg!();
+                    Some((job, borrowed)) = self.queue.pop_with_nodes(&nodes, &self.jobs) => {
+                        let n = nodes.len();
+                        info!("dispatch job {}, {n} slots left for computations", job.0.job_name());
+                        if let Some(id) = job.0.job_id() {
+                            let nodes = borrowed.iter().map(|node| node.name().into()).collect();
+                            self.jobs.events().send(Event::JobDispatched { id, nodes });
+                        }
+                        let task = compute_on_nodes(job, borrowed, nodes.clone(), self.queue.clone(), self.jobs.clone());
+                        running.spawn(task);
+                    }
+                    Some(ret) = running.join_next() => {
+                        if let Err(err) = ret {
+                            error!("job task panicked: {err:?}");
+                        }
                     }
                     Some(int) = rx_int.recv() => {
-                        tx_jobs.send(int)?;
+                        self.queue.push(int);
                     }
                     Some(ctl) = rx_ctl.recv() => {
                         match ctl {
-                            RemoteIO(Control::AddNode(node), _) => {
+                            RemoteIO(Control::AddNode(node), tx) => {
                                 info!("client asked to add a new remote node: {node:?}");
-                                let nodes = nodes.clone();
-                                nodes.return_node(node.into())?;
+                                let name = node.name().into();
+                                nodes.add_node(node)?;
+                                self.jobs.events().send(Event::NodeAdded { node: name });
+                                tx.send(()).ok();
                             }
-                            RemoteIO(Control::Abort, _) => {
-                                join_handler.abort();
+                            RemoteIO(Control::Shutdown { wait, workers }, tx) => {
+                                health_check.abort();
+                                self.shutdown(running, wait).await;
+                                // launched workers are always retired
+                                pool_stopped.cancel();
+                                if let Some(pool) = pool {
+                                    pool.await.ok();
+                                }
+                                if workers {
+                                    shutdown_workers(&nodes).await;
+                                }
+                                self.jobs.events().close();
+                                tx.send(()).ok();
                                 break;
                             },
                         }
@@ -185,17 +699,58 @@ mod server {
 // [[file:../../remote.note::231ad4be][231ad4be]]
 /// Create task server and client. The client can be cloned and used in
 /// concurrent environment
-pub(super) fn new_interactive_task() -> (TaskServer, TaskClient) {
+pub(super) fn new_interactive_task(nodes: &Nodes, config: &SchedulerConfig) -> (TaskServer, TaskClient) {
     let (rx_int, tx_int) = Task::new().split();
     let (rx_ctl, tx_ctl) = Task::new().split();
 
+    let jobs = JobRegistry::default().with_retention(config.keep_finished, config.keep_age);
+    let queue = JobQueue::default()
+        .with_locality_wait(config.locality_wait)
+        .with_backfill_wait(config.backfill_wait);
+
+    let arrays = ArrayRegistry::default();
+
     let server = TaskServer {
         rx_int: rx_int.into(),
         rx_ctl: rx_ctl.into(),
+        jobs: jobs.clone(),
+        queue: queue.clone(),
     };
 
-    let client = TaskClient { tx_int, tx_ctl };
+    let client = TaskClient {
+        tx_int,
+        tx_ctl,
+        jobs,
+        queue,
+        arrays,
+        cache: ResultCache::new(config.cache_size, config.cache_age),
+        limits: JobLimits::new(config.max_queued, config.max_client_jobs),
+        nodes: nodes.clone(),
+        started: std::time::Instant::now(),
+        shutting_down: CancellationToken::new(),
+    };
 
     (server, client)
 }
 // 231ad4be ends here
+
+// [[file:../../remote.note::38b76680][38b76680]]
+#[tokio::test]
+async fn test_check_nodes() {
+    use futures_util::StreamExt;
+
+    // nobody listening on the ports
+    let node: Node = "127.0.0.1:1".into();
+    let nodes = Nodes::new([node.clone(), "127.0.0.1:2".into()]);
+    let events = EventBus::default();
+    let stream = events.subscribe();
+    server::check_nodes(&nodes, &events).await;
+    // announced only once
+    server::check_nodes(&nodes, &events).await;
+    assert!(nodes.usage().iter().all(|x| x.down));
+    events.close();
+    let events: Vec<_> = stream.collect().await;
+    assert_eq!(events.len(), 2);
+    assert!(events.contains(&Event::NodeDown { node: node.name().into() }));
+}
+// 38b76680 ends here
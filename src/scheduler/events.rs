@@ -0,0 +1,214 @@
+// [[file:../../remote.note::02d3bdd1][02d3bdd1]]
+#![deny(warnings)]
+
+use super::*;
+use registry::{JobId, JobState};
+
+use futures_util::Stream;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_util::sync::CancellationToken;
+// 02d3bdd1 ends here
+
+// [[file:../../remote.note::e33a6c6e][e33a6c6e]]
+/// The number of events buffered for each subscriber. Slow subscribers
+/// will miss older events.
+const EVENT_CAPACITY: usize = 1024;
+
+/// The lifecycle event of jobs and nodes in the scheduler
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// The job was put in queue, on submission or for retrying.
+    JobQueued { id: JobId, name: String },
+    /// The job was popped from queue with nodes borrowed for it.
+    JobDispatched { id: JobId, nodes: Vec<String> },
+    /// The job was started on remote node.
+    JobStarted { id: JobId, node: String },
+    /// The job completed successfully.
+    JobCompleted { id: JobId },
+    /// The job failed, or was killed for running out of time.
+    JobFailed { id: JobId, timed_out: bool },
+    /// The job was cancelled.
+    JobCancelled { id: JobId },
+    /// The node was registered for computation.
+    NodeAdded { node: String },
+    /// The node was removed from the scheduler.
+    NodeRemoved { node: String },
+    /// The node was found unreachable, and will run no job until up.
+    NodeDown { node: String },
+    /// The node was reachable again after down.
+    NodeUp { node: String },
+    /// The subscriber was too slow, and missed some events.
+    Lagged { missed: u64 },
+}
+
+impl Event {
+    /// Construct the event for job `id` finished in `state`.
+    pub(super) fn job_finished(id: JobId, state: JobState) -> Self {
+        match state {
+            JobState::Completed => Self::JobCompleted { id },
+            JobState::Cancelled => Self::JobCancelled { id },
+            state => Self::JobFailed {
+                id,
+                timed_out: state == JobState::TimedOut,
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::JobQueued { id, name } => write!(f, "job {id} queued: {name}"),
+            Self::JobDispatched { id, nodes } => write!(f, "job {id} dispatched to {}", nodes.join(",")),
+            Self::JobStarted { id, node } => write!(f, "job {id} started on {node}"),
+            Self::JobCompleted { id } => write!(f, "job {id} completed"),
+            Self::JobFailed { id, timed_out: true } => write!(f, "job {id} timed out"),
+            Self::JobFailed { id, .. } => write!(f, "job {id} failed"),
+            Self::JobCancelled { id } => write!(f, "job {id} cancelled"),
+            Self::NodeAdded { node } => write!(f, "node {node} added"),
+            Self::NodeRemoved { node } => write!(f, "node {node} removed"),
+            Self::NodeDown { node } => write!(f, "node {node} down"),
+            Self::NodeUp { node } => write!(f, "node {node} up"),
+            Self::Lagged { missed } => write!(f, "{missed} events missed"),
+        }
+    }
+}
+
+/// Broadcast lifecycle events to all subscribers
+#[derive(Clone)]
+pub(super) struct EventBus {
+    tx: broadcast::Sender<Event>,
+    // cancelled when no more events will be sent
+    closed: CancellationToken,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        let (tx, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            tx,
+            closed: CancellationToken::new(),
+        }
+    }
+}
+
+impl EventBus {
+    /// Send `event` to all current subscribers, if any.
+    pub fn send(&self, event: Event) {
+        trace!("event: {event}");
+        // it is not an error if nobody is listening
+        let _ = self.tx.send(event);
+    }
+
+    /// Close the bus. Event streams will end after delivering pending
+    /// events.
+    pub fn close(&self) {
+        self.closed.cancel();
+    }
+
+    /// Subscribe to events sent from now on, until the bus closed.
+    pub fn subscribe(&self) -> impl Stream<Item = Event> {
+        let rx = self.tx.subscribe();
+        futures_util::stream::unfold((rx, self.closed.clone()), |(mut rx, closed)| async move {
+            let event = tokio::select! {
+                // pending events are always delivered before closing
+                biased;
+                ret = rx.recv() => match ret {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(missed)) => Event::Lagged { missed },
+                    Err(RecvError::Closed) => return None,
+                },
+                _ = closed.cancelled() => return None,
+            };
+            Some((event, (rx, closed)))
+        })
+    }
+}
+// e33a6c6e ends here
+
+// [[file:../../remote.note::ccff7d17][ccff7d17]]
+/// Parse the event carried in SSE message `block`. Return None for
+/// messages without data, e.g. keep-alive comments.
+fn parse_sse_message(block: &str) -> Result<Option<Event>> {
+    let data = block
+        .lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .map(|line| line.strip_prefix(' ').unwrap_or(line))
+        .join("\n");
+    if data.is_empty() {
+        return Ok(None);
+    }
+    let event = serde_json::from_str(&data).with_context(|| format!("invalid event: {data:?}"))?;
+    Ok(Some(event))
+}
+
+/// The live stream of events from the scheduler
+pub struct EventStream {
+    resp: reqwest::Response,
+    buf: Vec<u8>,
+}
+
+impl EventStream {
+    /// Wait for the next event. Return None when the scheduler closed
+    /// the stream.
+    pub async fn next(&mut self) -> Result<Option<Event>> {
+        loop {
+            // SSE messages are separated by blank lines
+            if let Some(i) = self.buf.windows(2).position(|w| w == b"\n\n") {
+                let block: Vec<u8> = self.buf.drain(..i + 2).collect();
+                if let Some(event) = parse_sse_message(&String::from_utf8_lossy(&block))? {
+                    return Ok(Some(event));
+                }
+                continue;
+            }
+            match self.resp.chunk().await? {
+                Some(chunk) => self.buf.extend_from_slice(&chunk),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+impl Client {
+    /// Subscribe to lifecycle events of jobs and nodes in the scheduler.
+    pub async fn watch_events(&self) -> Result<EventStream> {
+        let resp = self.get_stream("events").await?;
+        Ok(EventStream { resp, buf: vec![] })
+    }
+}
+// ccff7d17 ends here
+
+// [[file:../../remote.note::fe524e85][fe524e85]]
+#[tokio::test]
+async fn test_event_bus() {
+    use futures_util::StreamExt;
+
+    let bus = EventBus::default();
+    // no subscribers yet
+    bus.send(Event::JobCompleted { id: 0 });
+
+    let stream = bus.subscribe();
+    bus.send(Event::job_finished(1, JobState::TimedOut));
+    bus.send(Event::NodeAdded { node: "localhost:3031".into() });
+    bus.send(Event::NodeDown { node: "localhost:3031".into() });
+    bus.close();
+    let events: Vec<_> = stream.collect().await;
+    assert_eq!(
+        events,
+        [
+            Event::JobFailed { id: 1, timed_out: true },
+            Event::NodeAdded { node: "localhost:3031".into() },
+            Event::NodeDown { node: "localhost:3031".into() },
+        ]
+    );
+    assert_eq!(events[0].to_string(), "job 1 timed out");
+    assert_eq!(events[2].to_string(), "node localhost:3031 down");
+
+    let json = serde_json::to_string(&events[1]).unwrap();
+    assert_eq!(json, r#"{"event":"node_added","node":"localhost:3031"}"#);
+    let block = format!("event: node_added\ndata: {json}\n\n");
+    assert_eq!(parse_sse_message(&block).unwrap(), Some(events[1].clone()));
+    assert_eq!(parse_sse_message(":\n\n").unwrap(), None);
+}
+// fe524e85 ends here
@@ -0,0 +1,160 @@
+// [[file:../../remote.note::745e46ae][745e46ae]]
+#![deny(warnings)]
+
+use super::*;
+use crate::rest::Overloaded;
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+// 745e46ae ends here
+
+// [[file:../../remote.note::331f4cc3][331f4cc3]]
+/// The delay in seconds asked for clients to retry rejected requests
+const RETRY_AFTER: u64 = 5;
+
+/// The limits on jobs accepted by the scheduler, for protecting it from
+/// runaway clients
+#[derive(Clone, Default)]
+pub(super) struct JobLimits {
+    // the number of unfinished jobs submitted from each client host
+    in_flight: Arc<Mutex<HashMap<IpAddr, usize>>>,
+    // the number of admitted jobs not dispatched yet, including those
+    // on the way to queue
+    pending: Arc<AtomicUsize>,
+    // the maximum number of jobs pending in queue. Unlimited if zero.
+    max_queued: usize,
+    // the maximum number of unfinished jobs from each client host.
+    // Unlimited if zero.
+    max_in_flight: usize,
+}
+
+/// The admission of an unfinished job, released when dropped
+pub(super) struct JobPermit {
+    in_flight: Arc<Mutex<HashMap<IpAddr, usize>>>,
+    client: Option<IpAddr>,
+    // taken once the job dispatched
+    pending: Option<Arc<AtomicUsize>>,
+}
+
+impl JobPermit {
+    /// Mark the job as dispatched, which no longer counts as pending.
+    pub fn dispatched(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            pending.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Drop for JobPermit {
+    fn drop(&mut self) {
+        self.dispatched();
+        let Some(client) = self.client else { return };
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if let Some(n) = in_flight.get_mut(&client) {
+            *n -= 1;
+            if *n == 0 {
+                in_flight.remove(&client);
+            }
+        }
+    }
+}
+
+impl JobLimits {
+    /// Construct limits accepting at most `max_queued` jobs in queue, and
+    /// at most `max_in_flight` unfinished jobs from each client host.
+    pub fn new(max_queued: usize, max_in_flight: usize) -> Self {
+        Self {
+            max_queued,
+            max_in_flight,
+            ..Default::default()
+        }
+    }
+
+    /// Check if `n` more jobs can be put into queue with `queued` jobs.
+    pub fn check_queue(&self, queued: usize, n: usize) -> Result<()> {
+        if self.max_queued > 0 && queued + n > self.max_queued {
+            let retry_after = RETRY_AFTER;
+            info!("rejected {n} jobs: {queued} jobs in queue");
+            return Err(Overloaded::QueueFull { retry_after }.into());
+        }
+        Ok(())
+    }
+
+    /// Admit `n` jobs from `client`. Return one permit for each job,
+    /// which should be held until the job finished. The jobs count as
+    /// pending in queue until their permits marked dispatched, so that
+    /// jobs admitted but not put into queue yet are not missed.
+    pub fn admit(&self, client: Option<IpAddr>, n: usize) -> Result<Vec<JobPermit>> {
+        let pending = self.pending.fetch_add(n, Ordering::SeqCst);
+        let admitted = self.check_queue(pending, n).and_then(|_| self.admit_client(client, n));
+        if admitted.is_err() {
+            self.pending.fetch_sub(n, Ordering::SeqCst);
+        }
+        admitted?;
+        let permit = || JobPermit {
+            in_flight: self.in_flight.clone(),
+            client,
+            pending: Some(self.pending.clone()),
+        };
+        Ok((0..n).map(|_| permit()).collect())
+    }
+
+    /// Count `n` more unfinished jobs from `client`, if not over the limit.
+    fn admit_client(&self, client: Option<IpAddr>, n: usize) -> Result<()> {
+        let Some(client) = client else { return Ok(()) };
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let count = in_flight.entry(client).or_default();
+        if self.max_in_flight > 0 && *count + n > self.max_in_flight {
+            let retry_after = RETRY_AFTER;
+            info!("rejected {n} jobs: {count} jobs unfinished from client {client}");
+            return Err(Overloaded::TooManyJobs { retry_after }.into());
+        }
+        *count += n;
+        Ok(())
+    }
+}
+// 331f4cc3 ends here
+
+// [[file:../../remote.note::3be6d733][3be6d733]]
+#[test]
+fn test_job_limits() {
+    let overloaded = |r: Result<Vec<JobPermit>>| r.err().and_then(|e| e.downcast_ref::<Overloaded>().cloned());
+
+    let limits = JobLimits::new(10, 3);
+    let a: IpAddr = [10, 0, 0, 1].into();
+    let b: IpAddr = [10, 0, 0, 2].into();
+    let permits = limits.admit(Some(a), 2).unwrap();
+    assert_eq!(permits.len(), 2);
+    assert!(matches!(
+        overloaded(limits.admit(Some(a), 2)),
+        Some(Overloaded::TooManyJobs { .. })
+    ));
+    // other clients are not affected
+    let permit_b = limits.admit(Some(b), 3).unwrap();
+    // released when jobs finished
+    drop(permits);
+    assert!(limits.admit(Some(a), 3).is_ok());
+    drop(permit_b);
+    assert!(limits.in_flight.lock().unwrap().is_empty());
+    assert_eq!(limits.pending.load(Ordering::SeqCst), 0);
+
+    // jobs admitted count as pending until dispatched
+    let mut permits = limits.admit(None, 9).unwrap();
+    assert!(matches!(
+        overloaded(limits.admit(None, 2)),
+        Some(Overloaded::QueueFull { .. })
+    ));
+    permits[0].dispatched();
+    let permit = limits.admit(None, 2).unwrap();
+    assert!(limits.admit(None, 1).is_err());
+    // released only once
+    permits[0].dispatched();
+    drop(permit);
+    assert_eq!(limits.pending.load(Ordering::SeqCst), 8);
+
+    let unlimited = JobLimits::default();
+    assert_eq!(unlimited.admit(Some(a), 1000).unwrap().len(), 1000);
+}
+// 3be6d733 ends here
@@ -0,0 +1,274 @@
+// [[file:../../remote.note::a4cb5eeb][a4cb5eeb]]
+#![deny(warnings)]
+
+use super::*;
+use base::Nodes;
+use events::Event;
+use queue::JobQueue;
+use registry::JobRegistry;
+
+use std::net::SocketAddr;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::process::{Child, Command};
+use tokio_util::sync::CancellationToken;
+// a4cb5eeb ends here
+
+// [[file:../../remote.note::c00fd63a][c00fd63a]]
+/// The label marking workers launched by the elastic pool, valued by the
+/// launch ID
+pub(crate) const ELASTIC_LABEL: &str = "gosh.elastic";
+
+/// The environment variable passing the launch ID to launched workers
+pub(crate) const ELASTIC_ID_VAR: &str = "GOSH_ELASTIC_ID";
+
+/// The environment variable passing the scheduler address to launched
+/// workers
+pub(crate) const SCHEDULER_VAR: &str = "GOSH_SCHEDULER";
+
+/// The time in seconds between two checks for scaling
+const SCALE_INTERVAL: f64 = 1.0;
+
+/// The time in seconds to wait for a launched worker to register
+const LAUNCH_TIMEOUT: f64 = 60.0;
+
+/// The time in seconds to wait for a retired worker to exit
+const RETIRE_TIMEOUT: f64 = 10.0;
+
+/// The settings of the elastic worker pool
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PoolConfig {
+    /// The command template for launching a worker, run with `sh -c`.
+    /// `{host}`, `{scheduler}` and `{id}` will be replaced with the host
+    /// to launch on, the scheduler address and the launch ID. The worker
+    /// should register with label `gosh.elastic={id}`, which is done by
+    /// `bootstrap as-worker` automatically for local processes.
+    pub launch: String,
+    /// The hosts to launch workers on in turn. Default to localhost.
+    pub hosts: Vec<String>,
+    /// The maximum number of workers launched at the same time
+    pub max_workers: usize,
+    /// Launch a new worker when more jobs than this in queue are ready
+    /// but cannot fit in available nodes
+    pub scale_up: usize,
+    /// Retire a launched worker when idle for this number of seconds
+    pub idle_timeout: f64,
+    /// The scheduler address for launched workers to register with.
+    /// Default to the address the scheduler listening on, which should
+    /// not be unspecified (e.g. 0.0.0.0).
+    pub advertise: Option<String>,
+}
+
+impl PoolConfig {
+    /// Return the command for launching worker `id` on `host`, which will
+    /// register with `scheduler`.
+    fn command(&self, host: &str, scheduler: &str, id: usize) -> String {
+        self.launch
+            .replace("{host}", host)
+            .replace("{scheduler}", scheduler)
+            .replace("{id}", &id.to_string())
+    }
+}
+
+/// Return the launch ID of `node` if it was launched by the pool.
+fn launch_id(node: &Node) -> Option<usize> {
+    node.labels().get(ELASTIC_LABEL)?.parse().ok()
+}
+
+/// A worker launched by the pool
+struct Launched {
+    id: usize,
+    // the launching process, None once exited
+    child: Option<Child>,
+    started: Instant,
+    // the name of the registered node, None before registration
+    node: Option<String>,
+}
+
+/// Workers launched when the queue grows and retired when idle
+pub(super) struct ElasticPool {
+    config: PoolConfig,
+    // the address for launched workers to register with
+    scheduler: String,
+    launched: Vec<Launched>,
+    // the ID for the next launch
+    next_id: usize,
+}
+
+impl ElasticPool {
+    /// Construct a pool launching workers as `config`, which will
+    /// register with the scheduler listening on `address`, unless another
+    /// address to advertise is set in `config`.
+    pub fn new(config: PoolConfig, address: SocketAddr) -> Result<Self> {
+        let scheduler = match &config.advertise {
+            Some(advertise) => advertise.clone(),
+            None => {
+                ensure!(
+                    !address.ip().is_unspecified(),
+                    "launched workers cannot register with unspecified address {address}, set the address to advertise"
+                );
+                address.to_string()
+            }
+        };
+        let pool = Self {
+            config,
+            scheduler,
+            launched: vec![],
+            next_id: 0,
+        };
+        Ok(pool)
+    }
+
+    /// Launch a new worker.
+    fn launch(&mut self) -> Result<()> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let hosts = &self.config.hosts;
+        let host = if hosts.is_empty() { "localhost" } else { &hosts[id % hosts.len()] };
+        let cmd = self.config.command(host, &self.scheduler, id);
+        info!("launch worker {id}: {cmd}");
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(&cmd)
+            .env(ELASTIC_ID_VAR, id.to_string())
+            .env(SCHEDULER_VAR, &self.scheduler)
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("failed to launch worker: {cmd}"))?;
+        self.launched.push(Launched {
+            id,
+            child: Some(child),
+            started: Instant::now(),
+            node: None,
+        });
+        Ok(())
+    }
+
+    /// Shut down `node` retired from the pool, and wait for its launching
+    /// process to exit.
+    async fn retire(&mut self, node: &Node) {
+        use tokio::time::timeout;
+
+        info!("retire worker on node {node}");
+        if let Err(err) = Client::connect(node).shutdown_worker(RETIRE_TIMEOUT).await {
+            warn!("failed to shut down worker on node {node}: {err:?}");
+        }
+        let Some(i) = self.launched.iter().position(|w| w.node.as_deref() == Some(node.name())) else {
+            return;
+        };
+        if let Some(mut child) = self.launched.remove(i).child {
+            let wait = Duration::from_secs_f64(RETIRE_TIMEOUT);
+            if timeout(wait, child.wait()).await.is_err() {
+                warn!("worker on node {node} not exited in time, killed");
+                child.kill().await.ok();
+            }
+        }
+    }
+
+    /// Launch or retire workers according to `queue` and `nodes`. Removed
+    /// nodes are announced on events of `registry`.
+    async fn scale(&mut self, nodes: &Nodes, queue: &JobQueue, registry: &JobRegistry) {
+        // bind launched workers with registered nodes
+        for node in nodes.registered() {
+            let Some(id) = launch_id(&node) else { continue };
+            if let Some(w) = self.launched.iter_mut().find(|w| w.id == id && w.node.is_none()) {
+                info!("launched worker {id} registered as node {node}");
+                w.node = Some(node.name().into());
+            }
+        }
+        // the launching process may exit early if the worker runs in
+        // background, so only give up workers not registered in time
+        let launch_timeout = Duration::from_secs_f64(LAUNCH_TIMEOUT);
+        self.launched.retain_mut(|w| {
+            if let Some(child) = &mut w.child {
+                if let Ok(Some(status)) = child.try_wait() {
+                    debug!("launching process of worker {} exited: {status}", w.id);
+                    w.child = None;
+                }
+            }
+            if w.node.is_none() && w.started.elapsed() > launch_timeout {
+                warn!("launched worker {} not registered in time, given up", w.id);
+                return false;
+            }
+            true
+        });
+
+        let idle = Duration::from_secs_f64(self.config.idle_timeout);
+        for node in nodes.retire_idle(idle, |node| launch_id(node).is_some()) {
+            self.retire(&node).await;
+            registry.events().send(Event::NodeRemoved { node: node.name().into() });
+        }
+
+        let pending = self.launched.iter().filter(|w| w.node.is_none()).count();
+        // jobs held on dependencies or waiting for their locality would
+        // not run on a new worker
+        let unfit = queue.count_unfit(nodes, registry);
+        if unfit > self.config.scale_up && pending == 0 && self.launched.len() < self.config.max_workers {
+            info!("{unfit} jobs in queue cannot fit, launch a new worker");
+            if let Err(err) = self.launch() {
+                error!("{err:?}");
+            }
+        }
+    }
+
+    /// Launch and retire workers on demand until `stopped` cancelled, then
+    /// retire all launched workers.
+    pub async fn run(mut self, nodes: Nodes, queue: JobQueue, registry: JobRegistry, stopped: CancellationToken) {
+        let interval = Duration::from_secs_f64(SCALE_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => self.scale(&nodes, &queue, &registry).await,
+                _ = stopped.cancelled() => break,
+            }
+        }
+        info!("stop elastic pool with {} launched workers", self.launched.len());
+        for node in nodes.retire_idle(Duration::ZERO, |node| launch_id(node).is_some()) {
+            self.retire(&node).await;
+            registry.events().send(Event::NodeRemoved { node: node.name().into() });
+        }
+        // workers never registered are killed on drop
+    }
+}
+// c00fd63a ends here
+
+// [[file:../../remote.note::fee04268][fee04268]]
+#[tokio::test]
+async fn test_elastic_pool() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let out = dir.path().join("launched");
+    let config = PoolConfig {
+        launch: format!("echo {{host}} {{id}} {{scheduler}} $GOSH_ELASTIC_ID $GOSH_SCHEDULER >> {}", out.display()),
+        hosts: vec!["node1".into(), "node2".into()],
+        max_workers: 2,
+        scale_up: 0,
+        idle_timeout: 0.0,
+        advertise: None,
+    };
+    assert!(ElasticPool::new(config.clone(), "0.0.0.0:3031".parse()?).is_err());
+    let advertised = PoolConfig {
+        advertise: Some("node0:3031".into()),
+        ..config.clone()
+    };
+    assert_eq!(ElasticPool::new(advertised, "0.0.0.0:3031".parse()?)?.scheduler, "node0:3031");
+
+    let mut pool = ElasticPool::new(config, "127.0.0.1:3031".parse()?)?;
+    pool.launch()?;
+    pool.launch()?;
+    for w in &mut pool.launched {
+        w.child.as_mut().unwrap().wait().await?;
+    }
+    let launched = std::fs::read_to_string(&out)?;
+    let lines = launched.lines().sorted().collect_vec();
+    assert_eq!(lines, ["node1 0 127.0.0.1:3031 0 127.0.0.1:3031", "node2 1 127.0.0.1:3031 1 127.0.0.1:3031"]);
+
+    // bind registered workers, and retire them when idle
+    let nodes = Nodes::new([Node::from("w0").with_label(ELASTIC_LABEL, "0"), "w1".into()]);
+    let queue = JobQueue::default();
+    pool.scale(&nodes, &queue, &JobRegistry::default()).await;
+    assert_eq!(nodes.registered().len(), 1);
+    assert_eq!(pool.launched.len(), 1);
+    assert_eq!(pool.launched[0].id, 1);
+    Ok(())
+}
+// fee04268 ends here
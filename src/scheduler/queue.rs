@@ -0,0 +1,648 @@
+// [[file:../../remote.note::9e9394d6][9e9394d6]]
+#![deny(warnings)]
+
+use super::*;
+use crate::task::RemoteIO;
+use registry::{Dependencies, JobRegistry};
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+// 9e9394d6 ends here
+
+// [[file:../../remote.note::52fe6362][52fe6362]]
+/// A job pending in queue, for inspection
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct QueuedJob {
+    /// The position in queue, starting from 0 for the next to dispatch
+    pub position: usize,
+    /// The job ID assigned by the scheduler. None for molecule
+    /// computations.
+    pub id: Option<JobId>,
+    /// The job name
+    pub name: String,
+    /// The priority for dispatching
+    pub priority: i32,
+}
+
+/// The request for reordering a job in queue
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct QueueUpdate {
+    /// Change the priority of the job. The job will be placed after all
+    /// jobs with the same priority.
+    pub priority: Option<i32>,
+    /// Move the job to position in queue. The job will take the priority
+    /// of its neighbor to keep the queue ordered.
+    pub position: Option<usize>,
+}
+
+struct Entry {
+    priority: i32,
+    job: RemoteIO<Jobx, String>,
+    // the time the job was put into queue
+    queued: Instant,
+}
+
+/// The queue of pending jobs, ordered by priority from high to low, and by
+/// arrival time among equal priorities.
+#[derive(Clone, Default)]
+pub(super) struct JobQueue {
+    inner: Arc<Mutex<Vec<Entry>>>,
+    // notified when jobs added or reordered
+    changed: Arc<Notify>,
+    // cancelled when the queue is closed for shutdown
+    closed: CancellationToken,
+    // the node which ran the last job for each locality key, and when
+    localities: Arc<Mutex<HashMap<String, (Node, Instant)>>>,
+    // the time in seconds a job waits for the node of its locality
+    locality_wait: f64,
+    // the time in seconds a job can be blocked before no job after it
+    // goes first
+    backfill_wait: f64,
+}
+
+/// The reason reported to pending jobs when the scheduler shuts down
+pub(super) const SHUTDOWN_REASON: &str = "scheduler shutting down";
+
+/// The maximum number of locality keys to remember
+const MAX_LOCALITIES: usize = 10000;
+
+/// Forget the locality keys in `localities` whose node is down or no
+/// longer registered, and then the oldest ones if more than `max` keys
+/// left. More keys than necessary are forgotten to prune less often.
+fn prune_localities(localities: &mut HashMap<String, (Node, Instant)>, nodes: &Nodes, max: usize) {
+    localities.retain(|_, (node, _)| nodes.is_up(node));
+    if localities.len() <= max {
+        return;
+    }
+    let n = localities.len() - max * 3 / 4;
+    let mut times: Vec<_> = localities.values().map(|(_, t)| *t).collect();
+    let (_, &mut cutoff, _) = times.select_nth_unstable(n - 1);
+    localities.retain(|_, (_, t)| *t > cutoff);
+}
+
+/// Return the nodes on which `job` failed before.
+fn failed_nodes(job: &Jobx, registry: &JobRegistry) -> Vec<Node> {
+    match job.job_id() {
+        Some(id) => registry.status(id).map(|s| s.failed_nodes).unwrap_or_default(),
+        None => vec![],
+    }
+}
+
+/// Insert `entry` into `entries` after all entries with equal or higher
+/// priority.
+fn insert_entry(entries: &mut Vec<Entry>, entry: Entry) {
+    let i = entries
+        .iter()
+        .position(|x| x.priority < entry.priority)
+        .unwrap_or(entries.len());
+    entries.insert(i, entry);
+}
+
+impl JobQueue {
+    /// Let jobs wait up to `wait` seconds for the node which ran the last
+    /// job with the same locality key.
+    pub fn with_locality_wait(mut self, wait: f64) -> Self {
+        self.locality_wait = wait;
+        self
+    }
+
+    /// Stop jobs from going before a job blocked for more than `wait`
+    /// seconds, so that nodes becoming free will be kept for it. Jobs
+    /// can always go before blocked ones if zero.
+    pub fn with_backfill_wait(mut self, wait: f64) -> Self {
+        self.backfill_wait = wait;
+        self
+    }
+
+    /// Return true if job in `entry` has been blocked for too long, and no
+    /// job after it should go first.
+    fn is_starving(&self, entry: &Entry) -> bool {
+        self.backfill_wait > 0.0 && entry.queued.elapsed().as_secs_f64() > self.backfill_wait
+    }
+
+    /// Return the node which ran the last job with the same locality key
+    /// as `job`, unless the job should avoid it. The key will be forgotten
+    /// if its node is down or no longer registered.
+    fn locality_node(&self, job: &Jobx, nodes: &Nodes, avoid: &[Node]) -> Option<Node> {
+        let key = job.locality()?;
+        let mut localities = self.localities.lock().unwrap();
+        let (node, _) = localities.get(key)?;
+        if !nodes.is_up(node) {
+            debug!("forget locality {key:?} as node {node} is gone");
+            localities.remove(key);
+            return None;
+        }
+        (!avoid.iter().any(|x| x.name() == node.name())).then(|| node.clone())
+    }
+
+    /// Return the earliest time a job waiting for the node of its locality
+    /// stops waiting.
+    fn locality_deadline(&self) -> Option<Instant> {
+        let wait = Duration::from_secs_f64(self.locality_wait);
+        let now = Instant::now();
+        let entries = self.inner.lock().unwrap();
+        let localities = self.localities.lock().unwrap();
+        entries
+            .iter()
+            .filter(|x| x.job.0.locality().is_some_and(|k| localities.contains_key(k)))
+            .map(|x| x.queued + wait)
+            .filter(|&t| t > now)
+            .min()
+    }
+
+    /// Put `job` into queue according to its priority. The job will be
+    /// answered with an error immediately if the queue has been closed.
+    pub fn push(&self, job: RemoteIO<Jobx, String>) {
+        if self.closed.is_cancelled() {
+            let RemoteIO(job, tx_resp) = job;
+            info!("queue closed, rejected job {}", job.job_name());
+            tx_resp.send(format!("job {} rejected: {SHUTDOWN_REASON}", job.job_name())).ok();
+            return;
+        }
+        let priority = job.0.priority();
+        let queued = Instant::now();
+        insert_entry(&mut self.inner.lock().unwrap(), Entry { priority, job, queued });
+        self.changed.notify_waiters();
+    }
+
+    /// Take out the first job in queue which is ready and can be placed on
+    /// available nodes, together with the nodes borrowed for it. A job
+    /// with lower priority may go first if the jobs before it cannot fit
+    /// in available nodes, unless one of them has been blocked for too
+    /// long. A job prefers the node which ran the last job
+    /// with the same locality key, and waits for it if busy, until the
+    /// locality wait expires. Jobs with failed dependencies will be
+    /// cancelled.
+    fn try_pop(&self, nodes: &Nodes, registry: &JobRegistry) -> Option<(RemoteIO<Jobx, String>, Vec<Node>)> {
+        let mut entries = self.inner.lock().unwrap();
+        entries.retain(|entry| {
+            let job = &entry.job.0;
+            let (depends_on, always_run) = job.dependencies();
+            match (job.job_id(), registry.check_dependencies(depends_on, always_run)) {
+                (Some(id), Dependencies::Failed(dep)) => {
+                    info!("cancel job {id} as its dependency {dep} failed");
+                    registry.cancel_queued(id, &format!("dependency {dep} failed")).ok();
+                    false
+                }
+                _ => true,
+            }
+        });
+        // no need to scan the queue when all nodes are occupied
+        if nodes.len() == 0 {
+            return None;
+        }
+        // the requirements found unable to fit, for skipping jobs alike
+        // quickly in a long queue
+        let mut unfit = vec![];
+        let mut popped = None;
+        for (i, entry) in entries.iter().enumerate() {
+            let job = &entry.job.0;
+            let (depends_on, always_run) = job.dependencies();
+            if registry.check_dependencies(depends_on, always_run) != Dependencies::Satisfied {
+                continue;
+            }
+            let requirements = job.requirements();
+            let (requires, affinity, n) = &requirements;
+            // prefer nodes other than those the job failed on before
+            let avoid = failed_nodes(job, registry);
+            if let Some(node) = self.locality_node(job, nodes, &avoid) {
+                if let Some(borrowed) = nodes.try_borrow_nodes_from(&node, requires, affinity, *n, &avoid) {
+                    popped = Some((i, borrowed));
+                    break;
+                }
+                if entry.queued.elapsed().as_secs_f64() < self.locality_wait {
+                    continue;
+                }
+            }
+            if !unfit.contains(&requirements) {
+                if let Some(borrowed) = nodes.try_borrow_nodes(requires, affinity, *n, &avoid) {
+                    popped = Some((i, borrowed));
+                    break;
+                }
+                unfit.push(requirements);
+            }
+            // or small jobs after it may take free nodes again and again
+            if self.is_starving(entry) {
+                debug!("job {} blocked for too long, no job after it goes first", job.job_name());
+                break;
+            }
+        }
+        let (i, borrowed) = popped?;
+        let entry = entries.remove(i);
+        if let Some(key) = entry.job.0.locality() {
+            let mut localities = self.localities.lock().unwrap();
+            localities.insert(key.into(), (borrowed[0].clone(), Instant::now()));
+            if localities.len() > MAX_LOCALITIES {
+                prune_localities(&mut localities, nodes, MAX_LOCALITIES);
+            }
+        }
+        Some((entry.job, borrowed))
+    }
+
+    /// Wait until a job in queue is ready and can be placed on available
+    /// nodes. Return the job and the nodes borrowed for it, or None if the
+    /// queue has been closed.
+    pub async fn pop_with_nodes(
+        &self,
+        nodes: &Nodes,
+        registry: &JobRegistry,
+    ) -> Option<(RemoteIO<Jobx, String>, Vec<Node>)> {
+        loop {
+            // register for notifications before checking to avoid missing
+            // any wake-up
+            let nodes_changed = nodes.changed().notified();
+            let queue_changed = self.changed.notified();
+            let job_finished = registry.finished().notified();
+            tokio::pin!(nodes_changed);
+            tokio::pin!(queue_changed);
+            tokio::pin!(job_finished);
+            nodes_changed.as_mut().enable();
+            queue_changed.as_mut().enable();
+            job_finished.as_mut().enable();
+
+            if self.closed.is_cancelled() {
+                return None;
+            }
+            if let Some(popped) = self.try_pop(nodes, registry) {
+                return Some(popped);
+            }
+            // wake up when a job stops waiting for the node of its locality
+            let deadline = self.locality_deadline();
+            let locality_expired = async {
+                match deadline {
+                    Some(t) => tokio::time::sleep_until(t.into()).await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                _ = locality_expired => {}
+                _ = nodes_changed => {}
+                _ = queue_changed => {}
+                _ = job_finished => {}
+                _ = self.closed.cancelled() => {}
+            }
+        }
+    }
+
+    /// Close the queue for shutdown. All pending jobs will be removed and
+    /// answered with an error, and no more jobs will be accepted.
+    pub fn close(&self, registry: &JobRegistry) {
+        self.closed.cancel();
+        let entries = std::mem::take(&mut *self.inner.lock().unwrap());
+        for Entry { job: RemoteIO(job, tx_resp), .. } in entries {
+            let msg = match job.job_id() {
+                Some(id) => {
+                    registry.cancel_queued(id, SHUTDOWN_REASON).ok();
+                    String::new()
+                }
+                None => format!("job {} rejected: {SHUTDOWN_REASON}", job.job_name()),
+            };
+            tx_resp.send(msg).ok();
+        }
+    }
+
+    /// Return the number of jobs in queue which are ready but cannot fit
+    /// in available nodes, excluding those waiting for the node of their
+    /// locality.
+    pub fn count_unfit(&self, nodes: &Nodes, registry: &JobRegistry) -> usize {
+        let entries = self.inner.lock().unwrap();
+        let mut unfit = vec![];
+        let mut fit = vec![];
+        let mut count = 0;
+        for entry in entries.iter() {
+            let job = &entry.job.0;
+            let (depends_on, always_run) = job.dependencies();
+            if registry.check_dependencies(depends_on, always_run) != Dependencies::Satisfied {
+                continue;
+            }
+            let waiting = entry.queued.elapsed().as_secs_f64() < self.locality_wait;
+            if waiting && self.locality_node(job, nodes, &[]).is_some() {
+                continue;
+            }
+            let requirements = job.requirements();
+            if unfit.contains(&requirements) {
+                count += 1;
+            } else if !fit.contains(&requirements) {
+                let (requires, affinity, n) = &requirements;
+                if nodes.can_fit_now(requires, affinity, *n) {
+                    fit.push(requirements);
+                } else {
+                    count += 1;
+                    unfit.push(requirements);
+                }
+            }
+        }
+        count
+    }
+
+    /// Return the number of jobs pending in queue.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().len()
+    }
+
+    /// Remove job `id` from queue. Return None if it is not in queue.
+    pub fn remove(&self, id: JobId) -> Option<RemoteIO<Jobx, String>> {
+        let mut entries = self.inner.lock().unwrap();
+        let i = entries.iter().position(|x| x.job.0.job_id() == Some(id))?;
+        Some(entries.remove(i).job)
+    }
+
+    /// Return all jobs pending in queue in dispatching order.
+    pub fn list(&self) -> Vec<QueuedJob> {
+        let entries = self.inner.lock().unwrap();
+        entries
+            .iter()
+            .enumerate()
+            .map(|(position, x)| QueuedJob {
+                position,
+                id: x.job.0.job_id(),
+                name: x.job.0.job_name(),
+                priority: x.priority,
+            })
+            .collect()
+    }
+
+    /// Reorder job `id` in queue as requested in `update`.
+    pub fn reorder(&self, id: JobId, update: &QueueUpdate) -> Result<()> {
+        let mut entries = self.inner.lock().unwrap();
+        let i = entries
+            .iter()
+            .position(|x| x.job.0.job_id() == Some(id))
+            .ok_or(format_err!("job {id} is not in queue"))?;
+        match (update.priority, update.position) {
+            (Some(priority), None) => {
+                let mut entry = entries.remove(i);
+                entry.priority = priority;
+                if let Jobx::Job(_, job) = &mut entry.job.0 {
+                    job.priority = priority;
+                }
+                insert_entry(&mut entries, entry);
+            }
+            (None, Some(position)) => {
+                let mut entry = entries.remove(i);
+                let position = position.min(entries.len());
+                let neighbor = entries.get(position).or_else(|| entries.last());
+                if let Some(neighbor) = neighbor {
+                    entry.priority = neighbor.priority;
+                }
+                if let Jobx::Job(_, job) = &mut entry.job.0 {
+                    job.priority = entry.priority;
+                }
+                entries.insert(position, entry);
+            }
+            _ => bail!("either priority or position should be specified"),
+        }
+        info!("job {id} reordered in queue: {update:?}");
+        drop(entries);
+        self.changed.notify_waiters();
+        Ok(())
+    }
+}
+// 52fe6362 ends here
+
+// [[file:../../remote.note::8943321c][8943321c]]
+#[test]
+fn test_job_queue() {
+    let queue = JobQueue::default();
+    let push = |id: JobId, priority: i32| {
+        let job = Job::new("test").with_name(&format!("job{id}")).with_priority(priority);
+        let (tx, _) = tokio::sync::oneshot::channel();
+        queue.push(RemoteIO(Jobx::Job(id, job), tx));
+    };
+    push(0, 0);
+    push(1, 0);
+    push(2, 10);
+    push(3, 0);
+    let order = |queue: &JobQueue| queue.list().into_iter().filter_map(|x| x.id).collect_vec();
+    // higher priority first, FIFO among equal priorities
+    assert_eq!(order(&queue), [2, 0, 1, 3]);
+
+    let update = QueueUpdate {
+        priority: Some(20),
+        ..Default::default()
+    };
+    queue.reorder(3, &update).unwrap();
+    assert_eq!(order(&queue), [3, 2, 0, 1]);
+    let update = QueueUpdate {
+        position: Some(0),
+        ..Default::default()
+    };
+    queue.reorder(1, &update).unwrap();
+    assert_eq!(order(&queue), [1, 3, 2, 0]);
+    assert_eq!(queue.list()[0].priority, 20);
+    assert!(queue.reorder(9, &update).is_err());
+
+    assert!(queue.remove(2).is_some());
+    assert!(queue.remove(2).is_none());
+    assert_eq!(order(&queue), [1, 3, 0]);
+
+    // the first job fitting in nodes goes first
+    let registry = JobRegistry::default();
+    let nodes = Nodes::new(["node1"]);
+    let (job, borrowed) = queue.try_pop(&nodes, &registry).unwrap();
+    assert_eq!(job.0.job_id(), Some(1));
+    assert_eq!(borrowed[0].name(), "node1");
+    assert!(queue.try_pop(&nodes, &registry).is_none());
+}
+
+#[test]
+fn test_job_queue_dependencies() {
+    use crate::worker::{ComputationResult, ErrorKind};
+
+    let queue = JobQueue::default();
+    let registry = JobRegistry::default();
+    let nodes = Nodes::new(["node1", "node2"]);
+    let id0 = registry.register("job0".into());
+    let id1 = registry.register("job1".into());
+    let id2 = registry.register("job2".into());
+    let push = |id: JobId, job: Job| {
+        let (tx, _) = tokio::sync::oneshot::channel();
+        queue.push(RemoteIO(Jobx::Job(id, job), tx));
+    };
+    push(id1, Job::new("test").with_dependencies(vec![id0], false));
+    push(id2, Job::new("test").with_dependencies(vec![id1], false));
+
+    // held until dependencies completed
+    assert!(queue.try_pop(&nodes, &registry).is_none());
+    registry.set_finished(id0, ComputationResult { stdout: "done".into(), ..Default::default() });
+    let (job, _) = queue.try_pop(&nodes, &registry).unwrap();
+    assert_eq!(job.0.job_id(), Some(id1));
+
+    // cancelled when dependency failed
+    registry.set_running(id1, &"node1".into(), "1-a");
+    registry.set_finished(id1, ComputationResult::error(ErrorKind::Failed, "oops"));
+    assert!(queue.try_pop(&nodes, &registry).is_none());
+    assert!(queue.list().is_empty());
+    assert_eq!(registry.status(id2).unwrap().state, JobState::Cancelled);
+}
+
+#[test]
+fn test_job_queue_unfit() {
+    let queue = JobQueue::default();
+    let registry = JobRegistry::default();
+    let nodes = Nodes::new((0..200).map(|i| format!("node{i}")));
+    let push = |id: JobId, job: Job| {
+        let (tx, _) = tokio::sync::oneshot::channel();
+        queue.push(RemoteIO(Jobx::Job(id, job), tx));
+    };
+    // thousands of jobs cannot fit, followed by one can
+    let affinity = Affinity {
+        selector: [("partition".to_owned(), "fat".to_owned())].into(),
+        ..Default::default()
+    };
+    for id in 0..5000 {
+        push(id, Job::new("test").with_affinity(affinity.clone()));
+    }
+    push(5000, Job::new("test").with_requires(Resources::default(), 10));
+    let (job, borrowed) = queue.try_pop(&nodes, &registry).unwrap();
+    assert_eq!(job.0.job_id(), Some(5000));
+    assert_eq!(borrowed.len(), 10);
+    assert!(queue.try_pop(&nodes, &registry).is_none());
+    assert_eq!(queue.list().len(), 5000);
+}
+
+#[test]
+fn test_job_queue_backfill() {
+    let queue = JobQueue::default().with_backfill_wait(0.1);
+    let registry = JobRegistry::default();
+    let nodes = Nodes::new(["node0"]);
+    let push = |id: JobId, job: Job| {
+        let (tx, _) = tokio::sync::oneshot::channel();
+        queue.push(RemoteIO(Jobx::Job(id, job), tx));
+    };
+    let req = Resources::default();
+    // a job requiring two nodes with higher priority
+    push(0, Job::new("test").with_requires(req.clone(), 2).with_priority(1));
+    push(1, Job::new("test"));
+    push(2, Job::new("test"));
+    // smaller jobs go first for a while
+    let (job, borrowed) = queue.try_pop(&nodes, &registry).unwrap();
+    assert_eq!(job.0.job_id(), Some(1));
+    nodes.return_nodes(&borrowed, &req).unwrap();
+
+    // but not when the large job blocked for too long
+    std::thread::sleep(std::time::Duration::from_secs_f64(0.2));
+    assert!(queue.try_pop(&nodes, &registry).is_none());
+    nodes.add_node("node1".into()).unwrap();
+    let (job, _) = queue.try_pop(&nodes, &registry).unwrap();
+    assert_eq!(job.0.job_id(), Some(0));
+}
+
+#[test]
+fn test_job_queue_locality() {
+    let queue = JobQueue::default().with_locality_wait(60.0);
+    let registry = JobRegistry::default();
+    let nodes = Nodes::new(["node0", "node1"]);
+    let push = |queue: &JobQueue, id: JobId, dir: &str| {
+        let (tx, _) = tokio::sync::oneshot::channel();
+        queue.push(RemoteIO(Jobx::Job(id, Job::new("test").with_locality(dir)), tx));
+    };
+    let req = Resources::default();
+
+    push(&queue, 0, "/a");
+    let (_, a) = queue.try_pop(&nodes, &registry).unwrap();
+    push(&queue, 1, "/b");
+    let (_, b) = queue.try_pop(&nodes, &registry).unwrap();
+    assert_ne!(a[0].name(), b[0].name());
+    nodes.return_nodes(&a, &req).unwrap();
+    nodes.return_nodes(&b, &req).unwrap();
+
+    // placed on the node last ran in the same directory, though fifo
+    // prefers the other one
+    push(&queue, 2, "/b");
+    let (_, borrowed) = queue.try_pop(&nodes, &registry).unwrap();
+    assert_eq!(borrowed[0].name(), b[0].name());
+
+    // wait for the busy node, without blocking other jobs
+    push(&queue, 3, "/b");
+    push(&queue, 4, "/c");
+    let (job, borrowed) = queue.try_pop(&nodes, &registry).unwrap();
+    assert_eq!(job.0.job_id(), Some(4));
+    assert_eq!(borrowed[0].name(), a[0].name());
+    assert!(queue.try_pop(&nodes, &registry).is_none());
+    assert!(queue.locality_deadline().is_some());
+
+    // run on other nodes without waiting
+    let queue = queue.with_locality_wait(0.0);
+    nodes.return_nodes(&a, &req).unwrap();
+    let (job, borrowed) = queue.try_pop(&nodes, &registry).unwrap();
+    assert_eq!(job.0.job_id(), Some(3));
+    assert_eq!(borrowed[0].name(), a[0].name());
+
+    // forget the node being down
+    let job = Jobx::Job(5, Job::new("test").with_locality("/b"));
+    assert!(queue.locality_node(&job, &nodes, &[]).is_some());
+    nodes.mark_down(&a[0]);
+    assert!(queue.locality_node(&job, &nodes, &[]).is_none());
+    assert_eq!(queue.localities.lock().unwrap().len(), 2);
+}
+
+#[test]
+fn test_job_queue_count_unfit() {
+    let queue = JobQueue::default().with_locality_wait(60.0);
+    let registry = JobRegistry::default();
+    let nodes = Nodes::new(["node0"]);
+    let push = |id: JobId, job: Job| {
+        let (tx, _) = tokio::sync::oneshot::channel();
+        queue.push(RemoteIO(Jobx::Job(id, job), tx));
+    };
+    let id0 = registry.register("job0".into());
+    push(id0, Job::new("test").with_locality("/a"));
+    let (_, borrowed) = queue.try_pop(&nodes, &registry).unwrap();
+    push(1, Job::new("test"));
+    push(2, Job::new("test"));
+    // waiting for the node of its locality
+    push(3, Job::new("test").with_locality("/a"));
+    // held on dependency
+    push(4, Job::new("test").with_dependencies(vec![id0], false));
+    assert_eq!(queue.len(), 4);
+    assert_eq!(queue.count_unfit(&nodes, &registry), 2);
+    nodes.return_nodes(&borrowed, &Resources::default()).unwrap();
+    assert_eq!(queue.count_unfit(&nodes, &registry), 0);
+}
+
+#[test]
+fn test_prune_localities() {
+    let nodes = Nodes::new(["node0", "node1"]);
+    let [node0, node1] = ["node0", "node1"].map(Node::from);
+    let mut localities = HashMap::new();
+    for i in 0..10 {
+        let node = if i % 2 == 0 { &node0 } else { &node1 };
+        let t = Instant::now() + Duration::from_secs(i);
+        localities.insert(format!("/{i}"), (node.clone(), t));
+    }
+    prune_localities(&mut localities, &nodes, 10);
+    assert_eq!(localities.len(), 10);
+
+    nodes.mark_down(&node1);
+    prune_localities(&mut localities, &nodes, 10);
+    assert_eq!(localities.len(), 5);
+    // the newest ones are kept
+    prune_localities(&mut localities, &nodes, 4);
+    assert_eq!(localities.len(), 3);
+    assert!(localities.contains_key("/8"));
+    assert!(!localities.contains_key("/0"));
+}
+
+#[test]
+fn test_job_queue_close() {
+    let queue = JobQueue::default();
+    let registry = JobRegistry::default();
+    let id0 = registry.register("job0".into());
+    let (tx, _) = tokio::sync::oneshot::channel();
+    queue.push(RemoteIO(Jobx::Job(id0, Job::new("test")), tx));
+    queue.close(&registry);
+    assert!(queue.list().is_empty());
+    let status = registry.status(id0).unwrap();
+    assert_eq!(status.state, JobState::Cancelled);
+
+    // rejected once closed
+    let (tx, mut rx) = tokio::sync::oneshot::channel();
+    queue.push(RemoteIO(Jobx::Job(1, Job::new("test")), tx));
+    assert!(queue.list().is_empty());
+    assert!(rx.try_recv().unwrap().contains(SHUTDOWN_REASON));
+}
+// 8943321c ends here
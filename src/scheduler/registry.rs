@@ -0,0 +1,552 @@
+// [[file:../../remote.note::0a9716be][0a9716be]]
+#![deny(warnings)]
+
+use super::*;
+use crate::base::JobUsage;
+use crate::worker::{ComputationResult, ErrorKind};
+use events::{Event, EventBus};
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
+use tokio::sync::{watch, Notify};
+// 0a9716be ends here
+
+// [[file:../../remote.note::cf3afd40][cf3afd40]]
+/// The unique ID of a job submitted to the scheduler
+pub type JobId = usize;
+
+/// The state of a job submitted to the scheduler
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum JobState {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+    TimedOut,
+}
+
+impl JobState {
+    /// Return true if the job is done, successfully or not.
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Completed | Self::Failed | Self::Cancelled | Self::TimedOut)
+    }
+}
+
+/// The status of a job submitted to the scheduler
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct JobStatus {
+    /// The job ID assigned by the scheduler
+    pub id: JobId,
+    /// The job name
+    pub name: String,
+    /// The current state of the job
+    pub state: JobState,
+    /// The remote node running the job
+    pub node: Option<Node>,
+    /// The token identifying the job on the remote node running it
+    #[serde(default)]
+    pub token: Option<String>,
+    /// The time when the job was submitted
+    pub submitted: SystemTime,
+    /// The time when the job was dispatched to remote node
+    pub started: Option<SystemTime>,
+    /// The time when the job finished
+    pub finished: Option<SystemTime>,
+    /// The remote nodes on which the job failed
+    pub failed_nodes: Vec<Node>,
+    /// The exit status and resource usage of the job process on remote
+    /// node, for accounting
+    #[serde(default)]
+    pub usage: Option<JobUsage>,
+}
+
+struct JobRecord {
+    status: JobStatus,
+    result: Option<ComputationResult>,
+    // the jobs this job depends on, held until this job finished
+    depends_on: Vec<JobId>,
+    // the number of unfinished jobs depending on this job
+    dependents: usize,
+    // the result has been dropped for exceeding the retention limits
+    expired: bool,
+}
+
+/// The state of dependencies of a job
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Dependencies {
+    /// Some dependencies are not finished yet.
+    Pending,
+    /// The job is ready for dispatching.
+    Satisfied,
+    /// The dependency with the ID failed, was cancelled, or is unknown.
+    Failed(JobId),
+}
+// cf3afd40 ends here
+
+// [[file:../../remote.note::d2adcdeb][d2adcdeb]]
+#[derive(Default)]
+struct Jobs {
+    next_id: JobId,
+    records: HashMap<JobId, watch::Sender<JobRecord>>,
+    // finished jobs in order of finishing, for evicting old records
+    finished: VecDeque<(JobId, Instant)>,
+    // the maximum number of finished jobs kept. Unlimited if zero.
+    max_finished: usize,
+    // the time in seconds a finished job is kept. Unlimited if zero.
+    max_age: f64,
+}
+
+impl Jobs {
+    /// Return the error for job `id` not found in records.
+    fn not_found(&self, id: JobId) -> Error {
+        if id < self.next_id {
+            format_err!("job {id} expired: finished jobs are kept for limited number and time")
+        } else {
+            format_err!("no such job {id}")
+        }
+    }
+
+    /// Release the records of jobs `depends_on` held by a dependent job.
+    fn release(&mut self, depends_on: &[JobId]) {
+        for id in depends_on {
+            let Some(tx) = self.records.get(id) else { continue };
+            let mut removable = false;
+            tx.send_if_modified(|r| {
+                r.dependents = r.dependents.saturating_sub(1);
+                removable = r.expired && r.dependents == 0;
+                false
+            });
+            if removable {
+                self.records.remove(id);
+            }
+        }
+    }
+
+    /// Record job `id` as finished, and evict records of finished jobs
+    /// exceeding the retention limits. A record still depended on by
+    /// unfinished jobs only drops its result, and will be removed when
+    /// released.
+    fn finish(&mut self, id: JobId) {
+        if let Some(tx) = self.records.get(&id) {
+            let mut depends_on = vec![];
+            tx.send_if_modified(|r| {
+                depends_on = std::mem::take(&mut r.depends_on);
+                false
+            });
+            self.release(&depends_on);
+        }
+        self.finished.push_back((id, Instant::now()));
+
+        while let Some(&(id, t)) = self.finished.front() {
+            let too_many = self.max_finished > 0 && self.finished.len() > self.max_finished;
+            let too_old = self.max_age > 0.0 && t.elapsed().as_secs_f64() > self.max_age;
+            if !too_many && !too_old {
+                break;
+            }
+            self.finished.pop_front();
+            let Some(tx) = self.records.get(&id) else { continue };
+            let mut removable = false;
+            tx.send_modify(|r| {
+                r.expired = true;
+                r.result = None;
+                removable = r.dependents == 0;
+            });
+            if removable {
+                debug!("job {id} expired");
+                self.records.remove(&id);
+            }
+        }
+    }
+}
+
+/// Book-keeping of all jobs submitted to the scheduler, shared between
+/// route handlers and the dispatcher.
+#[derive(Clone, Default)]
+pub(super) struct JobRegistry {
+    inner: Arc<Mutex<Jobs>>,
+    // notified when any job finished
+    finished: Arc<Notify>,
+    // for broadcasting lifecycle events of jobs
+    events: EventBus,
+}
+
+impl JobRegistry {
+    /// Keep at most `max_finished` finished jobs, each for at most
+    /// `max_age` seconds. Querying an evicted job will fail as expired.
+    /// Unlimited if zero.
+    pub fn with_retention(self, max_finished: usize, max_age: f64) -> Self {
+        {
+            let mut jobs = self.inner.lock().unwrap();
+            jobs.max_finished = max_finished;
+            jobs.max_age = max_age;
+        }
+        self
+    }
+
+    /// Register a new queued job named as `name`. Return its job ID.
+    pub fn register(&self, name: String) -> JobId {
+        let mut jobs = self.inner.lock().unwrap();
+        let id = jobs.next_id;
+        jobs.next_id += 1;
+        let status = JobStatus {
+            id,
+            name,
+            state: JobState::Queued,
+            node: None,
+            token: None,
+            submitted: SystemTime::now(),
+            started: None,
+            finished: None,
+            failed_nodes: vec![],
+            usage: None,
+        };
+        self.events.send(Event::JobQueued {
+            id,
+            name: status.name.clone(),
+        });
+        let record = JobRecord {
+            status,
+            result: None,
+            depends_on: vec![],
+            dependents: 0,
+            expired: false,
+        };
+        let (tx, _) = watch::channel(record);
+        jobs.records.insert(id, tx);
+        crate::metrics::scheduler().job_submitted();
+        id
+    }
+
+    /// Keep the records of jobs `depends_on` until job `id` finished, for
+    /// checking dependencies of job `id` while it is held in queue.
+    pub fn hold_dependencies(&self, id: JobId, depends_on: &[JobId]) {
+        let jobs = self.inner.lock().unwrap();
+        let held = depends_on
+            .iter()
+            .copied()
+            .filter(|dep| {
+                let Some(tx) = jobs.records.get(dep) else {
+                    return false;
+                };
+                tx.send_if_modified(|r| {
+                    r.dependents += 1;
+                    false
+                });
+                true
+            })
+            .collect_vec();
+        if let Some(tx) = jobs.records.get(&id) {
+            tx.send_if_modified(|r| {
+                r.depends_on = held;
+                false
+            });
+        }
+    }
+
+    /// Apply `modify` on the record of job `id`. Waiters on the job will
+    /// be notified only if `modify` returns true.
+    fn update(&self, id: JobId, modify: impl FnOnce(&mut JobRecord) -> bool) -> bool {
+        let jobs = self.inner.lock().unwrap();
+        if let Some(tx) = jobs.records.get(&id) {
+            tx.send_if_modified(modify)
+        } else {
+            warn!("update a non-existing job {id}");
+            false
+        }
+    }
+
+    /// Mark job `id` running on remote `node`, identified there by
+    /// `token`. Return false if the job is not in queue any more, e.g.
+    /// cancelled already.
+    pub fn set_running(&self, id: JobId, node: &Node, token: &str) -> bool {
+        self.update(id, |r| {
+            if r.status.state != JobState::Queued {
+                return false;
+            }
+            r.status.state = JobState::Running;
+            r.status.node = Some(node.clone());
+            r.status.token = Some(token.into());
+            let now = SystemTime::now();
+            r.status.started = Some(now);
+            let wait = now.duration_since(r.status.submitted).unwrap_or_default();
+            crate::metrics::scheduler().job_started(wait.as_secs_f64());
+            self.events.send(Event::JobStarted {
+                id,
+                node: node.name().into(),
+            });
+            true
+        })
+    }
+
+    /// Put running job `id` back to queue, e.g. when its node is down.
+    /// Return false if the job is not running any more.
+    pub fn set_queued(&self, id: JobId) -> bool {
+        self.update(id, |r| {
+            if r.status.state != JobState::Running {
+                return false;
+            }
+            r.status.state = JobState::Queued;
+            r.status.node = None;
+            r.status.token = None;
+            r.status.started = None;
+            self.events.send(Event::JobQueued {
+                id,
+                name: r.status.name.clone(),
+            });
+            true
+        })
+    }
+
+    /// Record a failure of job `id` on `node`. Return the number of
+    /// failures so far.
+    pub fn add_failed_node(&self, id: JobId, node: &Node) -> usize {
+        let mut n = 0;
+        self.update(id, |r| {
+            r.status.failed_nodes.push(node.clone());
+            n = r.status.failed_nodes.len();
+            false
+        });
+        n
+    }
+
+    /// Mark job `id` finished with computation `result`. The result will be
+    /// ignored if the job has been finished already.
+    pub fn set_finished(&self, id: JobId, result: ComputationResult) {
+        let finished = self.update(id, |r| {
+            if r.status.state.is_finished() {
+                return false;
+            }
+            r.status.state = result.state();
+            let now = SystemTime::now();
+            r.status.finished = Some(now);
+            r.status.usage = result.usage;
+            let mut result = result;
+            if result.node.is_none() {
+                result.node = r.status.node.as_ref().map(|n| n.name().into());
+            }
+            r.result = Some(result);
+            let runtime = r.status.started.and_then(|t| now.duration_since(t).ok());
+            crate::metrics::scheduler().job_finished(r.status.state, runtime.map(|t| t.as_secs_f64()));
+            self.events.send(Event::job_finished(id, r.status.state));
+            true
+        });
+        if finished {
+            self.inner.lock().unwrap().finish(id);
+        }
+        self.finished.notify_waiters();
+    }
+
+    /// Cancel job `id` for `reason` if it is still in queue. Return its
+    /// current status.
+    pub fn cancel_queued(&self, id: JobId, reason: &str) -> Result<JobStatus> {
+        let msg = format!("job {id} cancelled before dispatched: {reason}");
+        let cancelled = self.update(id, |r| {
+            if r.status.state != JobState::Queued {
+                return false;
+            }
+            r.status.state = JobState::Cancelled;
+            r.status.finished = Some(SystemTime::now());
+            r.result = Some(ComputationResult::error(ErrorKind::Cancelled, msg));
+            crate::metrics::scheduler().job_finished(JobState::Cancelled, None);
+            self.events.send(Event::JobCancelled { id });
+            true
+        });
+        // the status is taken before the record may be evicted
+        let status = self.status(id);
+        if cancelled {
+            self.inner.lock().unwrap().finish(id);
+            self.finished.notify_waiters();
+        }
+        status
+    }
+
+    /// Return a notifier which will wake up all waiters when any job
+    /// finished.
+    pub fn finished(&self) -> &Notify {
+        &self.finished
+    }
+
+    /// Return the bus for broadcasting lifecycle events of jobs and nodes.
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Check the state of jobs `depends_on`. If `always_run` is true, the
+    /// dependencies are satisfied once all of them finished, successfully
+    /// or not.
+    pub fn check_dependencies(&self, depends_on: &[JobId], always_run: bool) -> Dependencies {
+        let mut pending = false;
+        for &id in depends_on {
+            let Ok(status) = self.status(id) else {
+                return Dependencies::Failed(id);
+            };
+            match status.state {
+                JobState::Completed => {}
+                state if state.is_finished() => {
+                    if !always_run {
+                        return Dependencies::Failed(id);
+                    }
+                }
+                _ => pending = true,
+            }
+        }
+        if pending {
+            Dependencies::Pending
+        } else {
+            Dependencies::Satisfied
+        }
+    }
+
+    /// Return the status of job `id`.
+    pub fn status(&self, id: JobId) -> Result<JobStatus> {
+        let jobs = self.inner.lock().unwrap();
+        let tx = jobs.records.get(&id).ok_or_else(|| jobs.not_found(id))?;
+        let status = tx.borrow().status.clone();
+        Ok(status)
+    }
+
+    /// Return true if the record of job `id` is still kept.
+    pub fn contains(&self, id: JobId) -> bool {
+        self.inner.lock().unwrap().records.contains_key(&id)
+    }
+
+    /// Return the status of all jobs in order of submission.
+    pub fn all(&self) -> Vec<JobStatus> {
+        let jobs = self.inner.lock().unwrap();
+        jobs.records
+            .iter()
+            .sorted_by_key(|(id, _)| **id)
+            .map(|(_, tx)| tx.borrow().status.clone())
+            .collect()
+    }
+
+    /// Wait until job `id` left queue, i.e. started or finished. Return
+    /// its current status.
+    pub async fn wait_for_start(&self, id: JobId) -> Result<JobStatus> {
+        let mut rx = {
+            let jobs = self.inner.lock().unwrap();
+            let tx = jobs.records.get(&id).ok_or_else(|| jobs.not_found(id))?;
+            tx.subscribe()
+        };
+        loop {
+            let status = rx.borrow_and_update().status.clone();
+            if status.state != JobState::Queued {
+                return Ok(status);
+            }
+            rx.changed().await?;
+        }
+    }
+
+    /// Wait until job `id` finished, and return its computation result.
+    pub async fn wait_for_result(&self, id: JobId) -> Result<ComputationResult> {
+        let mut rx = {
+            let jobs = self.inner.lock().unwrap();
+            let tx = jobs.records.get(&id).ok_or_else(|| jobs.not_found(id))?;
+            tx.subscribe()
+        };
+        loop {
+            let (result, expired) = {
+                let r = rx.borrow_and_update();
+                (r.result.clone(), r.expired)
+            };
+            if let Some(result) = result {
+                return Ok(result);
+            }
+            ensure!(!expired, "job {id} expired: its result is not kept any more");
+            rx.changed().await?;
+        }
+    }
+}
+// d2adcdeb ends here
+
+// [[file:../../remote.note::d98e098a][d98e098a]]
+#[test]
+fn test_job_registry() {
+    let jobs = JobRegistry::default();
+    let id1 = jobs.register("job1".into());
+    let id2 = jobs.register("job2".into());
+    assert_ne!(id1, id2);
+    assert_eq!(jobs.status(id1).unwrap().state, JobState::Queued);
+
+    let node: Node = "localhost:3031".into();
+    assert!(jobs.set_running(id1, &node, "1-a"));
+    let status = jobs.status(id1).unwrap();
+    assert_eq!(status.state, JobState::Running);
+    assert_eq!(status.node.unwrap().name(), "localhost:3031");
+
+    let usage = JobUsage {
+        exit_code: Some(0),
+        ..Default::default()
+    };
+    let result = ComputationResult {
+        stdout: "done".into(),
+        usage: Some(usage),
+        ..Default::default()
+    };
+    jobs.set_finished(id1, result);
+    let status = jobs.status(id1).unwrap();
+    assert!(status.state.is_finished());
+    assert_eq!(status.usage, Some(usage));
+    assert!(status.finished.is_some());
+    assert!(jobs.status(99).is_err());
+    assert_eq!(jobs.add_failed_node(id1, &node), 1);
+    assert_eq!(jobs.status(id1).unwrap().failed_nodes.len(), 1);
+
+    // a cancelled job will never be dispatched
+    let status = jobs.cancel_queued(id2, "test").unwrap();
+    assert_eq!(status.state, JobState::Cancelled);
+    assert!(!jobs.set_running(id2, &node, "2-a"));
+    jobs.set_finished(id2, ComputationResult { stdout: "done".into(), ..Default::default() });
+    assert_eq!(jobs.status(id2).unwrap().state, JobState::Cancelled);
+}
+
+#[test]
+fn test_job_dependencies() {
+    let jobs = JobRegistry::default();
+    let id1 = jobs.register("job1".into());
+    let id2 = jobs.register("job2".into());
+    assert_eq!(jobs.check_dependencies(&[], false), Dependencies::Satisfied);
+    assert_eq!(jobs.check_dependencies(&[id1, id2], false), Dependencies::Pending);
+    assert_eq!(jobs.check_dependencies(&[id1, 99], false), Dependencies::Failed(99));
+
+    jobs.set_finished(id1, ComputationResult { stdout: "done".into(), ..Default::default() });
+    assert_eq!(jobs.check_dependencies(&[id1], false), Dependencies::Satisfied);
+    jobs.set_finished(id2, ComputationResult::error(ErrorKind::Failed, "oops"));
+    assert_eq!(jobs.check_dependencies(&[id1, id2], false), Dependencies::Failed(id2));
+    // run anyway when all finished
+    assert_eq!(jobs.check_dependencies(&[id1, id2], true), Dependencies::Satisfied);
+}
+
+#[tokio::test]
+async fn test_job_registry_retention() {
+    let jobs = JobRegistry::default().with_retention(2, 0.0);
+    let done = || ComputationResult { stdout: "done".into(), ..Default::default() };
+    let id0 = jobs.register("job0".into());
+    let id1 = jobs.register("job1".into());
+    let id2 = jobs.register("job2".into());
+    // job2 is held in queue for job0
+    jobs.hold_dependencies(id2, &[id0]);
+    let id3 = jobs.register("job3".into());
+    let id4 = jobs.register("job4".into());
+    for id in [id0, id1, id3, id4] {
+        jobs.set_finished(id, done());
+    }
+
+    // the oldest finished jobs expired
+    let err = jobs.status(id1).unwrap_err();
+    assert!(err.to_string().contains("expired"), "{err}");
+    assert!(jobs.wait_for_result(id1).await.is_err());
+    assert!(jobs.status(99).unwrap_err().to_string().contains("no such job"));
+    assert_eq!(jobs.wait_for_result(id4).await.unwrap().stdout, "done");
+    // but the dependency of a queued job is kept without its result
+    assert_eq!(jobs.check_dependencies(&[id0], false), Dependencies::Satisfied);
+    let err = jobs.wait_for_result(id0).await.unwrap_err();
+    assert!(err.to_string().contains("expired"), "{err}");
+    // until the dependent job finished
+    jobs.cancel_queued(id2, "test").unwrap();
+    assert!(jobs.status(id0).is_err());
+    assert_eq!(jobs.all().len(), 2);
+}
+// d98e098a ends here
@@ -0,0 +1,167 @@
+// [[file:../../remote.note::64ed4638][64ed4638]]
+#![deny(warnings)]
+
+use super::*;
+use base::NodeUsage;
+
+use std::time::SystemTime;
+// 64ed4638 ends here
+
+// [[file:../../remote.note::9a1929e2][9a1929e2]]
+/// The state of a remote node registered in the scheduler
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum NodeState {
+    /// No job running on the node
+    Idle,
+    /// Some slots of the node are occupied
+    Busy,
+    /// All slots of the node are occupied
+    Full,
+    /// The node is unreachable
+    Down,
+}
+
+/// The status of a remote node registered in the scheduler
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NodeStatus {
+    /// The remote node
+    pub node: Node,
+    /// The current state of the node
+    pub state: NodeState,
+    /// The number of jobs running on the node
+    pub running: usize,
+    /// The resources allocated for running jobs
+    pub used: Resources,
+    /// The IDs of jobs running on the node
+    pub jobs: Vec<JobId>,
+}
+
+/// A job queued or running in the scheduler
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ActiveJob {
+    /// The job ID assigned by the scheduler
+    pub id: JobId,
+    /// The job name
+    pub name: String,
+    /// The current state of the job
+    pub state: JobState,
+    /// The name of remote node running the job
+    pub node: Option<String>,
+    /// The time in seconds since the job was submitted
+    pub age: f64,
+    /// The time in seconds since the job was dispatched
+    pub elapsed: Option<f64>,
+}
+
+/// The overall status of the scheduler
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SchedulerStatus {
+    /// The time in seconds since the scheduler started
+    pub uptime: f64,
+    /// The number of jobs pending in queue
+    pub queued: usize,
+    /// The number of jobs running on remote nodes
+    pub running: usize,
+    /// The number of jobs finished, successfully or not
+    pub finished: usize,
+    /// All registered remote nodes
+    pub nodes: Vec<NodeStatus>,
+    /// All jobs queued or running
+    pub jobs: Vec<ActiveJob>,
+}
+
+/// Return the time in seconds elapsed since `t`.
+fn seconds_since(t: SystemTime) -> f64 {
+    t.elapsed().map(|d| d.as_secs_f64()).unwrap_or_default()
+}
+
+impl SchedulerStatus {
+    /// Summarize status of the scheduler from usage of registered
+    /// `nodes`, status of all submitted `jobs`, and the number of jobs
+    /// `queued`.
+    pub(super) fn summarize(uptime: f64, queued: usize, nodes: Vec<NodeUsage>, jobs: Vec<JobStatus>) -> Self {
+        let nodes = nodes
+            .into_iter()
+            .map(|usage| {
+                let name = usage.node.name();
+                let running_jobs = jobs
+                    .iter()
+                    .filter(|j| j.state == JobState::Running && j.node.as_ref().map(|n| n.name()) == Some(name))
+                    .map(|j| j.id)
+                    .collect();
+                let state = if usage.down {
+                    NodeState::Down
+                } else if usage.running == 0 {
+                    NodeState::Idle
+                } else if usage.running < usage.node.slots() {
+                    NodeState::Busy
+                } else {
+                    NodeState::Full
+                };
+                NodeStatus {
+                    state,
+                    running: usage.running,
+                    used: usage.used,
+                    jobs: running_jobs,
+                    node: usage.node,
+                }
+            })
+            .collect();
+
+        let finished = jobs.iter().filter(|j| j.state.is_finished()).count();
+        let jobs = jobs
+            .into_iter()
+            .filter(|j| !j.state.is_finished())
+            .map(|j| ActiveJob {
+                id: j.id,
+                age: seconds_since(j.submitted),
+                elapsed: j.started.map(seconds_since),
+                node: j.node.map(|n| n.name().to_owned()),
+                name: j.name,
+                state: j.state,
+            })
+            .collect_vec();
+        let running = jobs.iter().filter(|j| j.state == JobState::Running).count();
+
+        Self {
+            uptime,
+            queued,
+            running,
+            finished,
+            nodes,
+            jobs,
+        }
+    }
+}
+// 9a1929e2 ends here
+
+// [[file:../../remote.note::83ce5db1][83ce5db1]]
+#[test]
+fn test_scheduler_status() {
+    use crate::worker::ComputationResult;
+    use registry::JobRegistry;
+
+    let nodes = Nodes::new(["node1", "node2"]);
+    nodes.mark_down(&"node2".into());
+    let registry = JobRegistry::default();
+    let id0 = registry.register("job0".into());
+    let id1 = registry.register("job1".into());
+    let _id2 = registry.register("job2".into());
+    let borrowed = nodes
+        .try_borrow_nodes(&Resources::default(), &Affinity::default(), 1, &[])
+        .unwrap();
+    registry.set_running(id1, &borrowed[0], "1-a");
+    registry.set_finished(id0, ComputationResult { stdout: "done".into(), ..Default::default() });
+
+    let status = SchedulerStatus::summarize(1.0, 1, nodes.usage(), registry.all());
+    assert_eq!(status.queued, 1);
+    assert_eq!(status.running, 1);
+    assert_eq!(status.finished, 1);
+    assert_eq!(status.jobs.len(), 2);
+    assert!(status.jobs[0].elapsed.is_some());
+    assert!(status.jobs[1].elapsed.is_none());
+    assert_eq!(status.nodes[0].state, NodeState::Full);
+    assert_eq!(status.nodes[0].jobs, [id1]);
+    assert_eq!(status.nodes[1].state, NodeState::Down);
+}
+// 83ce5db1 ends here
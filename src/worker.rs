@@ -1,6 +1,7 @@
 // [[file:../remote.note::4b6cf6fa][4b6cf6fa]]
 use super::*;
-use base::{Job, Node};
+use base::{Job, JobUsage, Node};
+use std::path::PathBuf;
 // 4b6cf6fa ends here
 
 // [[file:../remote.note::cfe8b623][cfe8b623]]
@@ -9,75 +10,493 @@ mod model;
 
 // [[file:../remote.note::0688d573][0688d573]]
 use gosh_model::Computed;
+use std::time::{Duration, SystemTime};
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
-pub enum ComputationResult {
-    JobCompleted(String),
-    JobFailed(String),
+/// The kind of error for a job not completed successfully
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// The job script exited with non-zero code or was killed, or the
+    /// job failed while running
+    Failed,
+    /// The job was terminated for running out of time
+    TimedOut,
+    /// The job was cancelled
+    Cancelled,
+    /// The job could not be started on the remote node
+    Spawn,
+    /// The result could not be received from the remote node
+    Transport,
+    /// The result was dropped by the scheduler for exceeding the
+    /// retention limits
+    Expired,
+}
+
+/// The error of a job not completed successfully
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct JobError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+/// The result of a job
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ComputationResult {
+    /// The standard output of the job
+    pub stdout: String,
+    /// The standard error of the job
+    pub stderr: String,
+    /// The exit code of the job script, if it exited normally
+    pub exit_code: Option<i32>,
+    /// The remote node running the job
+    pub node: Option<String>,
+    /// The working directory of the job on the remote node
+    pub wrk_dir: Option<PathBuf>,
+    /// The time when the job process started
+    pub started: Option<SystemTime>,
+    /// The time when the job finished
+    pub finished: Option<SystemTime>,
+    /// The exit status and resource usage of the job process
+    pub usage: Option<JobUsage>,
+    /// The error if the job did not complete successfully
+    pub error: Option<JobError>,
 }
 
 impl ComputationResult {
+    /// The result of a job not completed for error of `kind`, explained in
+    /// `message`.
+    pub(crate) fn error(kind: ErrorKind, message: impl Into<String>) -> Self {
+        let message = message.into();
+        Self {
+            error: Some(JobError { kind, message }),
+            finished: Some(SystemTime::now()),
+            ..Default::default()
+        }
+    }
+
+    /// Collect the result of `comput` finished with `error` if any.
+    fn from_computation(comput: &base::Computation, error: Option<JobError>) -> Self {
+        let (stdout, stderr) = comput.read_output().unwrap_or_default();
+        let usage = comput.usage();
+        let finished = SystemTime::now();
+        let started = usage.map(|u| finished - Duration::from_secs_f64(u.wall_time));
+        Self {
+            stdout,
+            stderr,
+            exit_code: usage.and_then(|u| u.exit_code),
+            node: None,
+            wrk_dir: comput.job_dir().into(),
+            started,
+            finished: finished.into(),
+            usage,
+            error,
+        }
+    }
+
     pub(crate) fn parse_from_json(x: &str) -> Result<Self> {
         let computed = serde_json::from_str(&x).with_context(|| format!("invalid json str: {x:?}"))?;
         Ok(computed)
     }
 
+    /// Return the final job state corresponding to the result.
+    pub fn state(&self) -> crate::JobState {
+        use crate::JobState;
+
+        match self.error.as_ref().map(|e| e.kind) {
+            None => JobState::Completed,
+            Some(ErrorKind::TimedOut) => JobState::TimedOut,
+            Some(ErrorKind::Cancelled) => JobState::Cancelled,
+            Some(_) => JobState::Failed,
+        }
+    }
+
+    /// Return the code for exiting like the job script run locally: its
+    /// exit code, 128 plus the signal number if killed by a signal, or 1
+    /// for other errors.
+    pub fn exit_status(&self) -> i32 {
+        if let Some(code) = self.exit_code {
+            code
+        } else if let Some(signal) = self.usage.and_then(|u| u.signal) {
+            128 + signal
+        } else if self.error.is_some() {
+            1
+        } else {
+            0
+        }
+    }
+
     pub fn get_computed_from_str(s: &str) -> Result<Computed> {
-        match Self::parse_from_json(s)? {
-            Self::JobCompleted(s) => {
-                let computed = s.parse()?;
-                Ok(computed)
-            }
-            Self::JobFailed(s) => {
-                bail!("job failed with error message:\n{s:?}");
-            }
+        let r = Self::parse_from_json(s)?;
+        if let Some(err) = r.error {
+            bail!("job failed ({:?}): {}\nstderr:\n{}", err.kind, err.message, r.stderr);
         }
+        let computed = r.stdout.parse()?;
+        Ok(computed)
     }
 }
 // 0688d573 ends here
 
+// [[file:../remote.note::e1bf6f25][e1bf6f25]]
+#[tokio::test]
+async fn test_computation_result() -> Result<()> {
+    use crate::JobState;
+
+    let mut comput = Job::new("#! /bin/sh\necho out\necho err >&2\nexit 3").submit()?;
+    let err = comput.wait_for_output().await.unwrap_err();
+    let error = JobError {
+        kind: ErrorKind::Failed,
+        message: format!("{err:#}"),
+    };
+    let r = ComputationResult::from_computation(&comput, Some(error));
+    assert_eq!(r.state(), JobState::Failed);
+    assert_eq!((r.stdout.as_str(), r.stderr.as_str()), ("out\n", "err\n"));
+    assert_eq!(r.exit_code, Some(3));
+    assert_eq!(r.exit_status(), 3);
+    assert!(r.started.is_some() && r.wrk_dir.is_some());
+
+    let r = ComputationResult::error(ErrorKind::TimedOut, "timeout");
+    assert_eq!(r.state(), JobState::TimedOut);
+    assert_eq!(r.exit_status(), 1);
+    let json = serde_json::to_string(&r)?;
+    assert!(json.contains(r#""error":{"kind":"timed_out","message":"timeout"}"#));
+    assert_eq!(ComputationResult::parse_from_json(&json)?, r);
+    Ok(())
+}
+// e1bf6f25 ends here
+
+// [[file:../remote.note::06e01069][06e01069]]
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+/// The time in seconds to remember a cancel request for a job not arrived
+/// yet
+const EARLY_CANCEL_TIMEOUT: f64 = 60.0;
+
+/// A job running on this worker
+struct RunningJob {
+    // for cancelling the job
+    cancel: CancellationToken,
+    // the file receiving standard output of the job
+    stdout: PathBuf,
+}
+
+/// Jobs running on this worker, which can be cancelled by their tokens
+/// assigned by the scheduler.
+#[derive(Clone, Default)]
+struct RunningJobs {
+    inner: Arc<Mutex<HashMap<String, RunningJob>>>,
+    // the jobs requested to cancel before arrived, and when
+    cancelled: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+/// Keep job running until dropped, when the job will be removed from
+/// running jobs and recorded as finished, even if the request for it was
+/// dropped halfway.
+struct RunningGuard {
+    jobs: RunningJobs,
+    token: String,
+    started: Instant,
+    // the final state of the job; cancelled if not set
+    state: Option<crate::JobState>,
+}
+
+impl RunningGuard {
+    /// Record the job finished in `state`.
+    fn finish(mut self, state: crate::JobState) {
+        self.state = Some(state);
+    }
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.jobs.remove(&self.token);
+        let state = self.state.unwrap_or(crate::JobState::Cancelled);
+        crate::metrics::worker().job_finished(state, self.started.elapsed().as_secs_f64());
+    }
+}
+
+impl RunningJobs {
+    /// Add job `token` into running jobs until the returned guard
+    /// dropped. The job will be cancelled at once if requested before.
+    fn insert(&self, token: &str, cancel: CancellationToken, stdout: PathBuf) -> Result<RunningGuard> {
+        let mut jobs = self.inner.lock().unwrap();
+        ensure!(!jobs.contains_key(token), "job {token:?} is already running on this worker");
+        if self.cancelled.lock().unwrap().remove(token).is_some() {
+            info!("cancel job {token:?} as requested before arrived");
+            cancel.cancel();
+        }
+        jobs.insert(token.into(), RunningJob { cancel, stdout });
+        crate::metrics::worker().job_started();
+        let guard = RunningGuard {
+            jobs: self.clone(),
+            token: token.into(),
+            started: Instant::now(),
+            state: None,
+        };
+        Ok(guard)
+    }
+
+    fn remove(&self, token: &str) {
+        self.inner.lock().unwrap().remove(token);
+    }
+
+    /// Return true if job `token` is still running.
+    fn contains(&self, token: &str) -> bool {
+        self.inner.lock().unwrap().contains_key(token)
+    }
+
+    /// Return the file receiving standard output of running job `token`.
+    fn stdout_file(&self, token: &str) -> Result<PathBuf> {
+        let jobs = self.inner.lock().unwrap();
+        let job = jobs.get(token).ok_or(format_err!("no running job {token:?}"))?;
+        Ok(job.stdout.clone())
+    }
+
+    /// Cancel running job `token`. A job not arrived yet, e.g. when the
+    /// request is sent right after dispatching, will be cancelled once
+    /// arrived within a timeout.
+    fn cancel(&self, token: &str) {
+        let jobs = self.inner.lock().unwrap();
+        if let Some(job) = jobs.get(token) {
+            info!("cancel running job {token:?}");
+            job.cancel.cancel();
+            return;
+        }
+        info!("job {token:?} not running, will be cancelled if arrived later");
+        let timeout = std::time::Duration::from_secs_f64(EARLY_CANCEL_TIMEOUT);
+        let mut cancelled = self.cancelled.lock().unwrap();
+        cancelled.retain(|_, t| t.elapsed() < timeout);
+        cancelled.insert(token.into(), Instant::now());
+    }
+
+    /// Cancel all running jobs.
+    fn cancel_all(&self) {
+        let jobs = self.inner.lock().unwrap();
+        for (name, job) in jobs.iter() {
+            info!("cancel running job {name:?}");
+            job.cancel.cancel();
+        }
+    }
+}
+
+#[test]
+fn test_running_jobs_dropped() -> Result<()> {
+    let jobs = RunningJobs::default();
+    let running = jobs.insert("job1", CancellationToken::new(), "job1.out".into())?;
+    assert!(jobs.contains("job1"));
+    // the same token cannot be used twice
+    assert!(jobs.insert("job1", CancellationToken::new(), "job1.out".into()).is_err());
+    running.finish(crate::JobState::Completed);
+    assert!(!jobs.contains("job1"));
+
+    // removed when the request dropped before the job finished
+    let running = jobs.insert("job2", CancellationToken::new(), "job2.out".into())?;
+    drop(running);
+    assert!(!jobs.contains("job2"));
+
+    // cancelled before arrived
+    jobs.cancel("job3");
+    let cancel = CancellationToken::new();
+    let _running = jobs.insert("job3", cancel.clone(), "job3.out".into())?;
+    assert!(cancel.is_cancelled());
+    Ok(())
+}
+// 06e01069 ends here
+
+// [[file:../remote.note::48e071c3][48e071c3]]
+use axum::body::Bytes;
+use futures_util::Stream;
+
+/// The time in seconds between polls for new output of a followed job
+const FOLLOW_INTERVAL: f64 = 0.5;
+
+/// The query for reading standard output of a job
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+pub(crate) struct OutputQuery {
+    /// Keep streaming new output until the job finished
+    #[serde(default)]
+    pub follow: bool,
+}
+
+/// The state for tailing standard output of a running job
+struct Tail {
+    jobs: RunningJobs,
+    token: String,
+    path: PathBuf,
+    // opened once the job created it
+    file: Option<tokio::fs::File>,
+    follow: bool,
+    // stop streaming after an error reported
+    failed: bool,
+}
+
+impl Tail {
+    /// Read the next chunk of output. Return None when no more output for
+    /// now, or when the output file is not created yet.
+    async fn read(&mut self) -> std::io::Result<Option<Bytes>> {
+        use tokio::io::AsyncReadExt;
+
+        if self.file.is_none() {
+            self.file = tokio::fs::File::open(&self.path).await.ok();
+        }
+        let Some(file) = self.file.as_mut() else {
+            return Ok(None);
+        };
+        let mut buf = vec![0; 8192];
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buf.truncate(n);
+        Ok(Some(buf.into()))
+    }
+}
+
+/// Stream standard output of running job `token` written so far. If
+/// `follow` is true, keep streaming new output until the job finished.
+fn tail_output(jobs: RunningJobs, token: &str, follow: bool) -> Result<impl Stream<Item = std::io::Result<Bytes>>> {
+    let path = jobs.stdout_file(token)?;
+    let tail = Tail {
+        jobs,
+        token: token.into(),
+        path,
+        file: None,
+        follow,
+        failed: false,
+    };
+    let stream = futures_util::stream::unfold(tail, |mut tail| async move {
+        if tail.failed {
+            return None;
+        }
+        loop {
+            // check before reading, so that no output written before the
+            // job finished will be missed
+            let running = tail.jobs.contains(&tail.token);
+            match tail.read().await {
+                Ok(Some(bytes)) => return Some((Ok(bytes), tail)),
+                Ok(None) if tail.follow && running => {
+                    tokio::time::sleep(std::time::Duration::from_secs_f64(FOLLOW_INTERVAL)).await;
+                }
+                Ok(None) => return None,
+                Err(err) => {
+                    tail.failed = true;
+                    return Some((Err(err), tail));
+                }
+            }
+        }
+    });
+    Ok(stream)
+}
+// 48e071c3 ends here
+
 // [[file:../remote.note::a2266f5f][a2266f5f]]
 mod handlers {
     use super::*;
     use crate::rest::AppError;
+    use axum::body::StreamBody;
+    use axum::extract::{Path, Query, State};
     use axum::Json;
 
     /// Run `job` locally and return stdout on success.
     #[axum::debug_handler]
-    pub(super) async fn create_job(Json(job): Json<Job>) -> Result<Json<ComputationResult>, AppError> {
+    pub(super) async fn create_job(
+        State(jobs): State<RunningJobs>,
+        Json(job): Json<Job>,
+    ) -> Result<Json<ComputationResult>, AppError> {
+        let name = job.name();
+        let token = job.token();
         match job.submit() {
-            Ok(mut comput) => match comput.wait_for_output().await {
-                Ok(out) => {
-                    let ret = ComputationResult::JobCompleted(out);
-                    debug!("computation done with: {ret:?}");
-                    Ok(Json(ret))
-                }
-                Err(err) => {
-                    let msg = format!("{err:?}");
-                    let ret = ComputationResult::JobFailed(msg);
-                    debug!("computation failed with: {ret:?}");
-                    Ok(Json(ret))
-                }
-            },
+            Ok(mut comput) => {
+                let running = match jobs.insert(&token, comput.cancel_token(), comput.out_file()) {
+                    Ok(running) => running,
+                    Err(err) => {
+                        error!("{err:#}");
+                        let ret = ComputationResult::error(ErrorKind::Spawn, format!("{err:#}"));
+                        return Ok(Json(ret));
+                    }
+                };
+                let error = match comput.wait_for_output().await {
+                    Ok(_) => None,
+                    Err(err) if comput.is_timed_out() => Some((ErrorKind::TimedOut, format!("{err:#}"))),
+                    Err(_) if comput.is_cancelled() => {
+                        Some((ErrorKind::Cancelled, format!("job {name:?} cancelled by request")))
+                    }
+                    Err(err) => Some((ErrorKind::Failed, format!("{err:#}"))),
+                };
+                let error = error.map(|(kind, message)| JobError { kind, message });
+                let ret = ComputationResult::from_computation(&comput, error);
+                debug!("computation done with: {ret:?}");
+                running.finish(ret.state());
+                Ok(Json(ret))
+            }
             Err(err) => {
-                let msg = format!("failed to create job: {err:?}");
+                let msg = format!("failed to create job: {err:#}");
                 error!("{msg}");
-                let ret = ComputationResult::JobFailed(msg);
+                let ret = ComputationResult::error(ErrorKind::Spawn, msg);
                 Ok(Json(ret))
             }
         }
     }
+
+    /// Stream standard output of running job `token`, following new
+    /// output until the job finished if requested.
+    #[axum::debug_handler]
+    pub(super) async fn get_stdout(
+        State(jobs): State<RunningJobs>,
+        Path(token): Path<String>,
+        Query(query): Query<OutputQuery>,
+    ) -> Result<StreamBody<impl Stream<Item = std::io::Result<Bytes>>>, AppError> {
+        let stream = tail_output(jobs, &token, query.follow)?;
+        Ok(StreamBody::new(stream))
+    }
+
+    /// Report the worker is alive.
+    pub(super) async fn health() -> &'static str {
+        "ok"
+    }
+
+    /// Cancel running job `token`, terminating all processes in its
+    /// session.
+    #[axum::debug_handler]
+    pub(super) async fn cancel_job(State(jobs): State<RunningJobs>, Path(token): Path<String>) -> Result<(), AppError> {
+        jobs.cancel(&token);
+        Ok(())
+    }
+
+    /// Shut down the worker after cancelling all running jobs, and confirm
+    /// with "ok".
+    #[axum::debug_handler]
+    pub(super) async fn shutdown(State((jobs, stopped)): State<(RunningJobs, CancellationToken)>) -> &'static str {
+        info!("shutdown requested");
+        jobs.cancel_all();
+        stopped.cancel();
+        "ok"
+    }
 }
 // a2266f5f ends here
 
 // [[file:../remote.note::57eb060f][57eb060f]]
-use self::handlers::create_job;
+use self::handlers::{cancel_job, create_job, get_stdout, health, shutdown};
 use axum::Router;
 
-fn app() -> Router {
-    use axum::routing::post;
+/// Build routes of the worker. `stopped` will be cancelled when shutdown
+/// requested.
+fn app(stopped: CancellationToken) -> Router {
+    use axum::routing::{delete, get, post};
 
-    Router::new().route("/jobs", post(create_job))
+    let jobs = RunningJobs::default();
+    Router::new()
+        .route("/jobs", post(create_job))
+        .route("/jobs/:token", delete(cancel_job))
+        .with_state(jobs.clone())
+        .route("/jobs/:token/stdout", get(get_stdout))
+        .with_state(jobs.clone())
+        .route("/shutdown", post(shutdown))
+        .with_state((jobs, stopped))
+        .route("/health", get(health))
+        .route("/metrics", get(crate::metrics::worker_metrics))
 }
 // 57eb060f ends here
 
@@ -98,6 +517,36 @@ impl RemoteComputation {
     }
 }
 
+impl Client {
+    /// Check if the remote worker is alive within `timeout` seconds.
+    pub(crate) async fn is_alive(&self, timeout: f64) -> bool {
+        let timeout = std::time::Duration::from_secs_f64(timeout);
+        matches!(tokio::time::timeout(timeout, self.get("health")).await, Ok(Ok(_)))
+    }
+
+    /// Wait until the remote worker is ready for jobs within `timeout`
+    /// seconds.
+    pub(crate) async fn wait_until_alive(&self, timeout: f64) -> Result<()> {
+        let now = std::time::Instant::now();
+        while !self.is_alive(timeout).await {
+            ensure!(now.elapsed().as_secs_f64() < timeout, "worker not ready in {timeout} seconds");
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        Ok(())
+    }
+
+    /// Request the remote worker to shut down, and wait for its
+    /// confirmation within `timeout` seconds.
+    pub(crate) async fn shutdown_worker(&self, timeout: f64) -> Result<()> {
+        let timeout = std::time::Duration::from_secs_f64(timeout);
+        let resp = tokio::time::timeout(timeout, self.post("shutdown", ()))
+            .await
+            .context("no response")??;
+        ensure!(resp == "ok", "unexpected response: {resp:?}");
+        Ok(())
+    }
+}
+
 impl Job {
     /// Remote submission using RESTful service
     pub fn submit_remote(self, node: &Node) -> Result<RemoteComputation> {
@@ -118,23 +567,22 @@ impl server::Server {
         let addr = self.address;
         println!("Start remote process serivce at {addr:?}");
         let signal = shutdown_signal();
-        let server = axum::Server::bind(&addr).serve(app().into_make_service());
-        let (tx, rx) = tokio::sync::oneshot::channel();
+        // finish in-flight requests when shutdown requested, in which
+        // running jobs have been cancelled
+        let stopped = CancellationToken::new();
+        let requested = stopped.clone();
+        let server = axum::Server::bind(&addr)
+            .serve(app(stopped).into_make_service())
+            .with_graceful_shutdown(async move { requested.cancelled().await });
         tokio::select! {
             _ = server => {
                 eprintln!("server closed");
             }
             _ = signal => {
-                let _ = tx.send(());
                 eprintln!("user interruption");
             }
         }
 
-        // FIXME: the below will cause immediate exit
-        // let server = axum::Server::bind(&addr)
-        //     .serve(app().into_make_service())
-        //     .with_graceful_shutdown(shutdown_signal());
-
         Ok(())
     }
 }
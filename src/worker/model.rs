@@ -51,7 +51,9 @@ pub(self) async fn serve_incoming_task_with(mut task: TaskReceiver, mut model: i
         debug!("wait for new molecule to compute ...");
         if let Some(RemoteIO(mol, tx_out)) = task.recv().await {
             debug!("ask client to compute molecule {}", mol.title());
-            if let Err(err) = compute_mol_and_send_out(&mol, &mut model, tx_out) {
+            let ret = compute_mol_and_send_out(&mol, &mut model, tx_out);
+            crate::metrics::worker().molecule_computed(ret.is_ok());
+            if let Err(err) = ret {
                 error!("{err:?}");
             }
         } else {
@@ -64,12 +66,12 @@ pub(self) async fn serve_incoming_task_with(mut task: TaskReceiver, mut model: i
 
 // [[file:../../remote.note::59c3364a][59c3364a]]
 macro_rules! build_app_with_routes {
-    ($state: expr) => {{
+    ($state: expr, $stopped: expr) => {{
         use axum::routing::post;
         axum::Router::new()
             .route("/mols", post(compute_mol))
             .with_state($state)
-            .route("/jobs", post(super::create_job))
+            .merge(super::app($stopped))
     }};
 }
 // 59c3364a ends here
@@ -107,11 +109,19 @@ use std::net::SocketAddr;
 /// * state: shared state between route handlers
 pub(self) async fn serve_mol_comput_requests(addr: impl Into<SocketAddr>, state: TaskState) {
     use crate::rest::shutdown_signal;
-
-    let app = build_app_with_routes!(state);
+    use tokio_util::sync::CancellationToken;
+
+    let stopped = CancellationToken::new();
+    let app = build_app_with_routes!(state, stopped.clone());
+    let signal = async move {
+        tokio::select! {
+            _ = shutdown_signal() => {}
+            _ = stopped.cancelled() => {}
+        }
+    };
     if let Err(err) = axum::Server::bind(&addr.into())
         .serve(app.into_make_service())
-        .with_graceful_shutdown(shutdown_signal())
+        .with_graceful_shutdown(signal)
         .await
     {
         error!("error in restful serivce: {err:?}");